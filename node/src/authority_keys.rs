@@ -0,0 +1,97 @@
+//! Check that a validator's keystore holds its keys of the current Aura and
+//! GRANDPA authority sets. Without them the node keeps running but silently
+//! stops authoring blocks or voting.
+//!
+//! The result is logged and exposed as `litentry_authority_key_in_keystore{key}`:
+//! `1` if the keystore has a key of the set, `0` if not.
+
+use std::{sync::Arc, time::Duration};
+use prometheus_endpoint::{register, GaugeVec, Opts, PrometheusError, Registry, U64};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_consensus_aura::{sr25519::AuthorityId as AuraId, AuraApi};
+use sp_core::crypto::{key_types, KeyTypeId, Public};
+use sp_finality_grandpa::GrandpaApi;
+use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
+use sp_runtime::generic::BlockId;
+use litentry_runtime::opaque::Block;
+
+/// How often the keys are checked after startup.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Checks the keystore against the authority sets at the best block.
+pub struct KeyMonitor<C> {
+	client: Arc<C>,
+	keystore: SyncCryptoStorePtr,
+	metric: Option<GaugeVec<U64>>,
+}
+
+impl<C> KeyMonitor<C> where
+	C: HeaderBackend<Block> + ProvideRuntimeApi<Block>,
+	C::Api: AuraApi<Block, AuraId> + GrandpaApi<Block>,
+{
+	/// Create a monitor, with its metric registered on `registry` if given.
+	pub fn new(client: Arc<C>, keystore: SyncCryptoStorePtr, registry: Option<&Registry>) -> Result<Self, PrometheusError> {
+		let metric = registry
+			.map(|registry| register(GaugeVec::new(
+				Opts::new(
+					"litentry_authority_key_in_keystore",
+					"Whether the keystore has a key of the current authority set, by key type",
+				),
+				&["key"],
+			)?, registry))
+			.transpose()?;
+
+		Ok(KeyMonitor { client, keystore, metric })
+	}
+
+	/// Check both authority sets at the best block.
+	pub fn check(&self) {
+		let at = BlockId::Hash(self.client.info().best_hash);
+		let api = self.client.runtime_api();
+
+		let aura = api.authorities(&at)
+			.map(|authorities| authorities.iter().map(Public::to_raw_vec).collect())
+			.map_err(|e| format!("{:?}", e));
+		self.check_set("aura", key_types::AURA, "author blocks", aura);
+
+		let grandpa = api.grandpa_authorities(&at)
+			.map(|authorities| authorities.iter().map(|(id, _)| id.to_raw_vec()).collect())
+			.map_err(|e| format!("{:?}", e));
+		self.check_set("grandpa", key_types::GRANDPA, "vote on finality", grandpa);
+	}
+
+	fn check_set(&self, name: &str, key_type: KeyTypeId, duty: &str, authorities: Result<Vec<Vec<u8>>, String>) {
+		let authorities = match authorities {
+			Ok(authorities) => authorities,
+			Err(e) => {
+				log::warn!("Failed to get the {} authorities: {}", name, e);
+				return;
+			},
+		};
+		let present = authorities.iter()
+			.any(|public| SyncCryptoStore::has_keys(&*self.keystore, &[(public.clone(), key_type)]));
+
+		if present {
+			log::debug!("The keystore has a {} key of the current authority set", name);
+		} else {
+			log::warn!(
+				"⚠️  The keystore has none of the {} {} authority keys, this validator won't {}! \
+				Insert its keys with `litentry-node key insert-session` if it is meant to be an authority.",
+				authorities.len(), name, duty,
+			);
+		}
+		if let Some(metric) = &self.metric {
+			metric.with_label_values(&[name]).set(present as u64);
+		}
+	}
+
+	/// Check the keys every [`CHECK_INTERVAL`] until the node stops.
+	pub async fn run(self) {
+		let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + CHECK_INTERVAL, CHECK_INTERVAL);
+		loop {
+			interval.tick().await;
+			self.check();
+		}
+	}
+}
@@ -0,0 +1,75 @@
+//! Block import wrapper enforcing the `forkBlocks` and `badBlocks` chain spec extensions.
+//!
+//! Full clients get this behaviour from `sc_service::new_full_parts`, the light client
+//! does not, so `new_light` puts this in front of its import pipeline.
+
+use std::collections::HashMap;
+use sc_client_api::{BadBlocks, ForkBlocks};
+use sp_consensus::{BlockCheckParams, BlockImport, BlockImportParams, ImportResult};
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor};
+
+/// Rejects blocks that the chain spec marks as bad or that conflict with a pinned fork block.
+pub struct ChainSpecBlockImport<Block: BlockT, I> {
+	inner: I,
+	fork_blocks: ForkBlocks<Block>,
+	bad_blocks: BadBlocks<Block>,
+}
+
+impl<Block: BlockT, I: Clone> Clone for ChainSpecBlockImport<Block, I> {
+	fn clone(&self) -> Self {
+		ChainSpecBlockImport {
+			inner: self.inner.clone(),
+			fork_blocks: self.fork_blocks.clone(),
+			bad_blocks: self.bad_blocks.clone(),
+		}
+	}
+}
+
+impl<Block: BlockT, I> ChainSpecBlockImport<Block, I> {
+	/// Wrap `inner`, rejecting blocks according to `fork_blocks` and `bad_blocks`.
+	pub fn new(inner: I, fork_blocks: ForkBlocks<Block>, bad_blocks: BadBlocks<Block>) -> Self {
+		ChainSpecBlockImport { inner, fork_blocks, bad_blocks }
+	}
+
+	fn is_known_bad(&self, number: NumberFor<Block>, hash: &Block::Hash) -> bool {
+		let conflicts_with_fork = self.fork_blocks.as_ref()
+			.and_then(|forks| forks.iter().find(|(n, _)| *n == number))
+			.map_or(false, |(_, expected)| expected != hash);
+		let is_bad = self.bad_blocks.as_ref().map_or(false, |bad| bad.contains(hash));
+
+		if conflicts_with_fork || is_bad {
+			log::warn!("Rejecting block #{} ({}) listed in the chain spec", number, hash);
+			return true;
+		}
+
+		false
+	}
+}
+
+impl<Block: BlockT, I: BlockImport<Block>> BlockImport<Block> for ChainSpecBlockImport<Block, I> {
+	type Error = I::Error;
+	type Transaction = I::Transaction;
+
+	fn check_block(
+		&mut self,
+		block: BlockCheckParams<Block>,
+	) -> Result<ImportResult, Self::Error> {
+		if self.is_known_bad(block.number, &block.hash) {
+			return Ok(ImportResult::KnownBad);
+		}
+
+		self.inner.check_block(block)
+	}
+
+	fn import_block(
+		&mut self,
+		block: BlockImportParams<Block, Self::Transaction>,
+		cache: HashMap<sp_consensus::import_queue::CacheKeyId, Vec<u8>>,
+	) -> Result<ImportResult, Self::Error> {
+		if self.is_known_bad(*block.header.number(), &block.post_hash()) {
+			return Ok(ImportResult::KnownBad);
+		}
+
+		self.inner.import_block(block, cache)
+	}
+}
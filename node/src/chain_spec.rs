@@ -4,18 +4,93 @@ use litentry_runtime::{
 	AccountId, AuraConfig, BalancesConfig, GenesisConfig, GrandpaConfig,
 	SudoConfig, SystemConfig, WASM_BINARY, Signature,
 	CouncilConfig, TechnicalCommitteeConfig, DemocracyConfig,
+	opaque::Block,
 };
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use sp_finality_grandpa::AuthorityId as GrandpaId;
 use sp_runtime::traits::{Verify, IdentifyAccount};
 use sc_service::{ChainType, Properties};
+use sc_chain_spec::ChainSpecExtension;
+use serde::{Serialize, Deserialize};
 use hex_literal::hex;
 
 // The URL for the telemetry server.
 // const STAGING_TELEMETRY_URL: &str = "wss://telemetry.polkadot.io/submit/";
 
+/// Node `ChainSpec` extensions.
+///
+/// Additional parameters for some Substrate core modules,
+/// customizable from the chain spec.
+#[derive(Default, Clone, Serialize, Deserialize, ChainSpecExtension)]
+#[serde(rename_all = "camelCase")]
+pub struct Extensions {
+	/// GRANDPA finality gadget parameters.
+	#[serde(default)]
+	pub grandpa: GrandpaParameters,
+	/// Block numbers with known hashes. Blocks at these heights with any other
+	/// hash are rejected on import.
+	#[serde(default)]
+	pub fork_blocks: sc_client_api::ForkBlocks<Block>,
+	/// Known bad block hashes, rejected on import.
+	#[serde(default)]
+	pub bad_blocks: sc_client_api::BadBlocks<Block>,
+	/// Slow down Aura authoring while finality lags behind. Disabled when absent.
+	#[serde(default)]
+	pub authoring_backoff: Option<AuthoringBackoffParameters>,
+}
+
+/// GRANDPA parameters that differ between networks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GrandpaParameters {
+	/// Interval between gossip rounds, in milliseconds.
+	pub gossip_duration_millis: u64,
+	/// Number of blocks between justifications stored in the database.
+	pub justification_period: u32,
+	/// Whether the GRANDPA observer protocol is enabled.
+	pub observer_enabled: bool,
+	/// Never vote on a block closer than this to the best block. `0` disables the rule.
+	pub before_best_block_by: u32,
+	/// Never vote past three quarters of the unfinalized chain.
+	pub three_quarters_of_unfinalized_chain: bool,
+}
+
+impl Default for GrandpaParameters {
+	fn default() -> Self {
+		GrandpaParameters {
+			gossip_duration_millis: 333,
+			justification_period: 512,
+			observer_enabled: false,
+			before_best_block_by: 2,
+			three_quarters_of_unfinalized_chain: true,
+		}
+	}
+}
+
+/// Aura authoring backoff, applied when the finalized head lags behind the best block.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AuthoringBackoffParameters {
+	/// Maximum number of slots to skip between authored blocks.
+	pub max_interval: u32,
+	/// Number of unfinalized blocks tolerated before backing off.
+	pub unfinalized_slack: u32,
+	/// Scales the backoff; higher values back off more slowly.
+	pub authoring_bias: u32,
+}
+
+impl Default for AuthoringBackoffParameters {
+	fn default() -> Self {
+		AuthoringBackoffParameters {
+			max_interval: 100,
+			unfinalized_slack: 50,
+			authoring_bias: 2,
+		}
+	}
+}
+
 /// Specialized `ChainSpec`. This is a specialization of the general Substrate ChainSpec type.
-pub type ChainSpec = sc_service::GenericChainSpec<GenesisConfig>;
+pub type ChainSpec = sc_service::GenericChainSpec<GenesisConfig, Extensions>;
 
 /// Generate a crypto pair from seed.
 pub fn get_from_seed<TPublic: Public>(seed: &str) -> <TPublic::Pair as Pair>::Public {
@@ -77,7 +152,7 @@ pub fn development_config() -> Result<ChainSpec, String> {
 		// Properties
 		Some(litentry_properties()),
 		// Extensions
-		None,
+		Default::default(),
 	))
 }
 
@@ -125,7 +200,7 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
 		// Properties
 		None,
 		// Extensions
-		None,
+		Default::default(),
 	))
 }
 
@@ -226,6 +301,9 @@ pub fn litentry_config() -> Result<ChainSpec, String> {
 		// Properties
 		Some(litentry_properties()),
 		// Extensions
-		None,
+		Extensions {
+			authoring_backoff: Some(Default::default()),
+			..Default::default()
+		},
 	))
 }
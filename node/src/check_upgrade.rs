@@ -0,0 +1,419 @@
+//! `check-upgrade`: compare the metadata and version of two runtime blobs and
+//! report what would break if one replaced the other on chain.
+//!
+//! Reported as problems, which make the command fail:
+//! - changed or removed calls without a `transaction_version` bump, since
+//!   transactions signed for the old encoding would decode differently,
+//! - changed or removed storage items not declared with `--migrated`,
+//! - a `spec_version` that isn't higher than the old one, which makes nodes
+//!   keep executing the old native runtime.
+//!
+//! Changed events are only noted, they matter to clients decoding them.
+//!
+//! Types are compared by their layout with every definition expanded, so a
+//! changed field of a struct counts as a change of everything using it. The
+//! metadata only names types, so the definitions each runtime was built with
+//! come from the types bundle of `export-types`, by `spec_version`, falling back
+//! to those built into the node.
+
+use std::{collections::{BTreeMap, BTreeSet}, fs, path::PathBuf};
+use structopt::StructOpt;
+use sc_cli::Error;
+use sp_core::hexdisplay::HexDisplay;
+use sp_version::RuntimeVersion;
+
+use crate::{
+	export_types::{Definition, TypesBundle, Variants},
+	scale::{self, Metadata, StorageEntryType, Ty, TYPES},
+	wasm_blob::WasmBlob,
+};
+
+#[derive(Debug, StructOpt)]
+pub struct CheckUpgradeCmd {
+	/// The runtime currently on chain.
+	#[structopt(long, value_name = "WASM", parse(from_os_str))]
+	pub old: PathBuf,
+
+	/// The runtime to upgrade to.
+	#[structopt(long, value_name = "WASM", parse(from_os_str))]
+	pub new: PathBuf,
+
+	/// A storage item the new runtime migrates, as `Pallet::Item`. Changes to it
+	/// aren't reported as problems.
+	#[structopt(long, value_name = "PALLET::ITEM")]
+	pub migrated: Vec<String>,
+
+	/// Types bundle written by `export-types`, with the type definitions of each spec version.
+	#[structopt(long, value_name = "PATH", default_value = "ts-tests/types.ts", parse(from_os_str))]
+	pub types: PathBuf,
+}
+
+#[derive(Default)]
+struct Report {
+	problems: Vec<String>,
+	notes: Vec<String>,
+}
+
+impl CheckUpgradeCmd {
+	/// Run the command.
+	pub fn run(&self) -> Result<(), Error> {
+		let old = WasmBlob::load(&self.old)?;
+		let new = WasmBlob::load(&self.new)?;
+		let (old_version, new_version) = (old.version()?, new.version()?);
+		let (old_metadata, new_metadata) = (old.metadata()?, new.metadata()?);
+
+		println!("Old: {} (0x{})", old_version, HexDisplay::from(&old.hash()));
+		println!("New: {} (0x{})", new_version, HexDisplay::from(&new.hash()));
+
+		let mut report = Report::default();
+		let bundle = match fs::read_to_string(&self.types) {
+			Ok(content) => Some(TypesBundle::parse(&content).map_err(|e| format!("{}: {}", self.types.display(), e))?),
+			Err(e) => {
+				report.notes.push(format!("Types bundle {} not read: {}", self.types.display(), e));
+				None
+			},
+		};
+		let old_types = Types::new(bundle.as_ref(), &old_version, &mut report);
+		let new_types = Types::new(bundle.as_ref(), &new_version, &mut report);
+
+		if old_version.spec_name != new_version.spec_name {
+			report.problems.push(format!(
+				"spec_name changed from {} to {}, nodes will refuse the upgrade",
+				old_version.spec_name, new_version.spec_name,
+			));
+		}
+		if new_version.spec_version <= old_version.spec_version {
+			report.problems.push(format!(
+				"spec_version {} isn't higher than the old {}",
+				new_version.spec_version, old_version.spec_version,
+			));
+		}
+
+		let call_changes = changes(&calls(&old_metadata, &old_types), &calls(&new_metadata, &new_types));
+		if !call_changes.is_empty() {
+			if new_version.transaction_version == old_version.transaction_version {
+				report.problems.push(listed(
+					format!("Call encoding changed but transaction_version is still {}", new_version.transaction_version),
+					&call_changes,
+				));
+			} else {
+				report.notes.push(listed(
+					format!(
+						"Call encoding changed, covered by transaction_version {} -> {}",
+						old_version.transaction_version, new_version.transaction_version,
+					),
+					&call_changes,
+				));
+			}
+		}
+
+		let old_storage = storage(&old_metadata, &old_types);
+		let new_storage = storage(&new_metadata, &new_types);
+		storage_changes(&old_storage, &new_storage, &self.migrated, &mut report);
+
+		let event_changes = changes(&events(&old_metadata, &old_types), &events(&new_metadata, &new_types));
+		if !event_changes.is_empty() {
+			report.notes.push(listed("Event encoding changed, clients need the new metadata to decode them".into(), &event_changes));
+		}
+
+		for note in &report.notes {
+			println!("note: {}", note);
+		}
+		for problem in &report.problems {
+			println!("problem: {}", problem);
+		}
+
+		match report.problems.len() {
+			0 => {
+				println!("The upgrade is compatible");
+				Ok(())
+			},
+			count => Err(format!("{} problem(s) with the upgrade", count).into()),
+		}
+	}
+}
+
+fn listed(header: String, items: &[String]) -> String {
+	items.iter().fold(format!("{}:", header), |listed, item| format!("{}\n    {}", listed, item))
+}
+
+/// Type definitions of one runtime.
+struct Types {
+	definitions: BTreeMap<String, Definition>,
+}
+
+impl Types {
+	/// The definitions of `version` in `bundle` over those built into the node.
+	fn new(bundle: Option<&TypesBundle>, version: &RuntimeVersion, report: &mut Report) -> Self {
+		let mut definitions = TYPES.iter()
+			.map(|(name, def)| (name.to_string(), Definition::from(def)))
+			.collect::<BTreeMap<_, _>>();
+
+		let spec_version = version.spec_version;
+		let versioned = bundle
+			.and_then(|bundle| bundle.spec.get(&*version.spec_name))
+			.and_then(|spec| spec.types.iter().find(|types| {
+				types.minmax.0 <= spec_version && types.minmax.1.map_or(true, |max| spec_version <= max)
+			}));
+		match versioned {
+			Some(types) => definitions.extend(types.types.0.iter().cloned()),
+			None => report.notes.push(format!(
+				"No types for spec version {} in the bundle, assuming the node's definitions",
+				spec_version,
+			)),
+		}
+
+		Types { definitions }
+	}
+
+	/// `ty` with every definition expanded, so types compare equal exactly if
+	/// they encode the same.
+	fn layout(&self, ty: &str) -> String {
+		match scale::parse(ty) {
+			Ok(ty) => self.expand(&ty, &mut BTreeSet::new()),
+			Err(_) => scale::canonical(ty),
+		}
+	}
+
+	fn expand(&self, ty: &Ty, expanding: &mut BTreeSet<String>) -> String {
+		match ty {
+			Ty::Compact(inner) => format!("Compact<{}>", self.expand(inner, expanding)),
+			Ty::Vec(inner) => format!("Vec<{}>", self.expand(inner, expanding)),
+			Ty::Option(inner) => format!("Option<{}>", self.expand(inner, expanding)),
+			Ty::Array(inner, len) => format!("[{}; {}]", self.expand(inner, expanding), len),
+			Ty::Tuple(types) => format!(
+				"({})",
+				types.iter().map(|ty| self.expand(ty, expanding)).collect::<Vec<_>>().join(", "),
+			),
+			Ty::Named(name) | Ty::Generic(name, _) if self.definitions.contains_key(name) => {
+				// Recursive types are named where they recur.
+				if !expanding.insert(name.clone()) {
+					return name.clone();
+				}
+				let expanded = self.expand_definition(&self.definitions[name], expanding);
+				expanding.remove(name);
+				expanded
+			},
+			Ty::Generic(name, params) => format!(
+				"{}<{}>",
+				name,
+				params.iter().map(|ty| self.expand(ty, expanding)).collect::<Vec<_>>().join(", "),
+			),
+			ty => scale::canonical_ty(ty),
+		}
+	}
+
+	fn expand_str(&self, ty: &str, expanding: &mut BTreeSet<String>) -> String {
+		match scale::parse(ty) {
+			Ok(ty) => self.expand(&ty, expanding),
+			Err(_) => scale::canonical(ty),
+		}
+	}
+
+	fn expand_definition(&self, definition: &Definition, expanding: &mut BTreeSet<String>) -> String {
+		match definition {
+			Definition::Alias(alias) => self.expand_str(alias, expanding),
+			Definition::Struct(fields) => format!(
+				"{{{}}}",
+				fields.0.iter()
+					.map(|(name, ty)| format!("{}: {}", name, self.expand_str(ty, expanding)))
+					.collect::<Vec<_>>()
+					.join(", "),
+			),
+			Definition::Enum { variants: Variants::Unit(variants) } => format!("enum {{{}}}", variants.join(", ")),
+			Definition::Enum { variants: Variants::Payload(variants) } => format!(
+				"enum {{{}}}",
+				variants.0.iter()
+					.map(|(name, ty)| match ty.as_str() {
+						"Null" => name.clone(),
+						ty => format!("{}({})", name, self.expand_str(ty, expanding)),
+					})
+					.collect::<Vec<_>>()
+					.join(", "),
+			),
+		}
+	}
+}
+
+/// Calls by `(pallet index, call index)`, with their name and the layouts of
+/// their arguments. Argument names don't affect the encoding and are left out.
+fn calls(metadata: &Metadata, types: &Types) -> BTreeMap<(u8, u8), String> {
+	metadata.modules.iter()
+		.flat_map(|module| module.calls.iter().flatten().enumerate().map(move |(index, call)| {
+			let arguments = call.arguments.iter().map(|arg| types.layout(&arg.ty)).collect::<Vec<_>>();
+			((module.index, index as u8), format!("{}::{}({})", module.name, call.name, arguments.join(", ")))
+		}))
+		.collect()
+}
+
+/// Events by `(pallet index, event index)`, with their name and the layouts of their fields.
+fn events(metadata: &Metadata, types: &Types) -> BTreeMap<(u8, u8), String> {
+	metadata.modules.iter()
+		.flat_map(|module| module.event.iter().flatten().enumerate().map(move |(index, event)| {
+			let arguments = event.arguments.iter().map(|ty| types.layout(ty)).collect::<Vec<_>>();
+			((module.index, index as u8), format!("{}::{}({})", module.name, event.name, arguments.join(", ")))
+		}))
+		.collect()
+}
+
+/// Storage items by `Prefix::Name`, with their hashers and the layouts of their types.
+fn storage(metadata: &Metadata, types: &Types) -> BTreeMap<String, String> {
+	metadata.modules.iter()
+		.filter_map(|module| module.storage.as_ref())
+		.flat_map(|storage| storage.entries.iter().map(move |entry| {
+			let layout = match &entry.ty {
+				StorageEntryType::Plain(value) => types.layout(value),
+				StorageEntryType::Map { hasher, key, value } =>
+					format!("map {:?}({}) => {}", hasher, types.layout(key), types.layout(value)),
+				StorageEntryType::DoubleMap { hasher, key1, key2, value, key2_hasher } => format!(
+					"double map {:?}({}), {:?}({}) => {}",
+					hasher, types.layout(key1), key2_hasher, types.layout(key2), types.layout(value),
+				),
+			};
+			(format!("{}::{}", storage.prefix, entry.name), layout)
+		}))
+		.collect()
+}
+
+/// Report storage items of `old` that were removed or changed in `new`, as
+/// problems unless declared `migrated`, and note the added ones.
+fn storage_changes(
+	old_storage: &BTreeMap<String, String>,
+	new_storage: &BTreeMap<String, String>,
+	migrated: &[String],
+	report: &mut Report,
+) {
+	for (item, old_layout) in old_storage {
+		let change = match new_storage.get(item) {
+			Some(new_layout) if new_layout == old_layout => continue,
+			Some(new_layout) => format!("Storage `{}` changed from {} to {}", item, old_layout, new_layout),
+			None => format!("Storage `{}` removed", item),
+		};
+		if migrated.contains(item) {
+			report.notes.push(format!("{}, migrated", change));
+		} else {
+			report.problems.push(format!("{} without a migration (declare one with `--migrated {}`)", change, item));
+		}
+	}
+	for item in new_storage.keys().filter(|item| !old_storage.contains_key(*item)) {
+		report.notes.push(format!("Storage `{}` added", item));
+	}
+	for item in migrated {
+		if old_storage.get(item) == new_storage.get(item) {
+			report.notes.push(format!("`{}` is declared migrated but its layout didn't change", item));
+		}
+	}
+}
+
+/// Entries of `old` that were removed or differ in `new`, described.
+fn changes(old: &BTreeMap<(u8, u8), String>, new: &BTreeMap<(u8, u8), String>) -> Vec<String> {
+	old.iter()
+		.filter_map(|(index, old)| match new.get(index) {
+			Some(new) if new == old => None,
+			Some(new) => Some(format!("[{}, {}] was {}, is now {}", index.0, index.1, old, new)),
+			None => Some(format!("[{}, {}] {} removed", index.0, index.1, old)),
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::export_types::Ordered;
+
+	fn types(definitions: &[(&str, Definition)]) -> Types {
+		Types { definitions: definitions.iter().map(|(name, def)| (name.to_string(), def.clone())).collect() }
+	}
+
+	fn fields(fields: &[(&str, &str)]) -> Definition {
+		Definition::Struct(Ordered(
+			fields.iter().map(|(name, ty)| (name.to_string(), ty.to_string())).collect(),
+		))
+	}
+
+	#[test]
+	fn aliases_expand_to_their_layout() {
+		let types = types(&[("Amount", Definition::Alias("u128".into()))]);
+
+		assert_eq!(types.layout("Amount"), types.layout("u128"));
+		assert_eq!(types.layout("Vec<(Amount, Compact<Amount>)>"), "Vec<(u128, Compact<u128>)>");
+	}
+
+	#[test]
+	fn changed_field_changes_every_user() {
+		let old = types(&[("Pair", fields(&[("a", "u32"), ("b", "Option<AccountId>")]))]);
+		let new = types(&[("Pair", fields(&[("a", "u32"), ("b", "u64")]))]);
+
+		assert_eq!(old.layout("Vec<Pair>"), "Vec<{a: u32, b: Option<AccountId>}>");
+		assert_ne!(old.layout("Vec<Pair>"), new.layout("Vec<Pair>"));
+		assert_eq!(old.layout("Vec<u32>"), new.layout("Vec<u32>"));
+	}
+
+	#[test]
+	fn recursive_types_are_named_where_they_recur() {
+		let types = types(&[("Tree", fields(&[("value", "u8"), ("children", "Vec<Tree>")]))]);
+
+		assert_eq!(types.layout("Tree"), "{value: u8, children: Vec<Tree>}");
+	}
+
+	#[test]
+	fn enum_payloads_expand() {
+		let types = types(&[
+			("Amount", Definition::Alias("u64".into())),
+			("Action", Definition::Enum { variants: Variants::Payload(Ordered(vec![
+				("Stop".into(), "Null".into()),
+				("Pay".into(), "Amount".into()),
+			])) }),
+		]);
+
+		assert_eq!(types.layout("Action"), "enum {Stop, Pay(u64)}");
+	}
+
+	#[test]
+	fn changes_list_changed_and_removed_calls() {
+		let old = vec![
+			((0, 0), "System::remark(Bytes)".to_string()),
+			((0, 1), "System::set_code(Bytes)".to_string()),
+			((1, 0), "Balances::transfer(AccountId, Compact<u128>)".to_string()),
+		].into_iter().collect();
+		let new = vec![
+			((0, 0), "System::remark(Bytes)".to_string()),
+			((1, 0), "Balances::transfer(AccountId, u128)".to_string()),
+			((1, 1), "Balances::burn(u128)".to_string()),
+		].into_iter().collect();
+
+		assert_eq!(changes(&old, &new), vec![
+			"[0, 1] System::set_code(Bytes) removed".to_string(),
+			"[1, 0] was Balances::transfer(AccountId, Compact<u128>), is now Balances::transfer(AccountId, u128)".to_string(),
+		]);
+		assert!(changes(&old, &old).is_empty());
+	}
+
+	#[test]
+	fn storage_changes_need_a_migration() {
+		let old = vec![
+			("System::Number".to_string(), "u32".to_string()),
+			("Linker::Links".to_string(), "map Blake2_128Concat(AccountId) => Vec<[u8; 20]>".to_string()),
+			("Linker::Expiry".to_string(), "u32".to_string()),
+		].into_iter().collect();
+		let new = vec![
+			("System::Number".to_string(), "u32".to_string()),
+			("Linker::Links".to_string(), "map Blake2_128Concat(AccountId) => Vec<[u8; 32]>".to_string()),
+			("Linker::Count".to_string(), "u32".to_string()),
+		].into_iter().collect();
+
+		let mut report = Report::default();
+		storage_changes(&old, &new, &[], &mut report);
+		assert_eq!(report.problems.len(), 2);
+		assert!(report.problems[0].starts_with("Storage `Linker::Expiry` removed"));
+		assert!(report.problems[1].starts_with("Storage `Linker::Links` changed"));
+		assert_eq!(report.notes, vec!["Storage `Linker::Count` added".to_string()]);
+
+		let mut report = Report::default();
+		let migrated = vec!["Linker::Links".to_string(), "System::Number".to_string()];
+		storage_changes(&old, &new, &migrated, &mut report);
+		assert_eq!(report.problems.len(), 1);
+		assert!(report.notes.iter().any(|note| note.starts_with("Storage `Linker::Links` changed") && note.ends_with(", migrated")));
+		assert!(report.notes.contains(&"`System::Number` is declared migrated but its layout didn't change".to_string()));
+	}
+}
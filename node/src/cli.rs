@@ -1,5 +1,14 @@
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
 use structopt::StructOpt;
-use sc_cli::RunCmd;
+use litentry_runtime::Balance;
+use sc_service::ChainType;
+use sp_core::sr25519;
+use crate::chain_spec::{AuthoringBackoffParameters, GrandpaParameters};
+use crate::faucet_endpoint::FaucetEndpointConfig;
+use crate::health::HealthConfig;
+use crate::ocw::{mock::HttpFixtures, OcwConfig, OcwConfigFile};
+use crate::rpc::faucet::FaucetConfig;
+use crate::tx::parse_public;
 
 #[derive(Debug, StructOpt)]
 pub struct Cli {
@@ -10,10 +19,291 @@ pub struct Cli {
 	pub run: RunCmd,
 }
 
+#[derive(Debug, StructOpt)]
+pub struct RunCmd {
+	#[structopt(flatten)]
+	pub base: sc_cli::RunCmd,
+
+	#[structopt(flatten)]
+	pub grandpa: GrandpaParams,
+
+	#[structopt(flatten)]
+	pub backoff: AuthoringBackoffParams,
+
+	#[structopt(flatten)]
+	pub ocw: OcwParams,
+
+	#[structopt(flatten)]
+	pub health: HealthParams,
+
+	/// Keep the events of finalized blocks indexed in this SQLite database, like `index` does.
+	#[structopt(long = "index-events", value_name = "PATH", parse(from_os_str))]
+	pub index_events: Option<PathBuf>,
+
+	/// Token server URL (`http://` only) to probe once per query session for the
+	/// `litentry_token_server_probe_*` metrics.
+	#[structopt(long = "token-server-probe", value_name = "URL")]
+	pub token_server_probe: Option<String>,
+
+	#[structopt(flatten)]
+	pub faucet: FaucetParams,
+}
+
+/// GRANDPA overrides. Anything left unset is taken from the chain spec.
+#[derive(Debug, StructOpt)]
+pub struct GrandpaParams {
+	/// Interval between GRANDPA gossip rounds, in milliseconds.
+	#[structopt(long = "grandpa-gossip-duration", value_name = "MILLIS")]
+	pub gossip_duration: Option<u64>,
+
+	/// Number of blocks between GRANDPA justifications stored in the database.
+	#[structopt(long = "grandpa-justification-period", value_name = "BLOCKS")]
+	pub justification_period: Option<u32>,
+
+	/// Enable or disable the GRANDPA observer protocol.
+	#[structopt(long = "grandpa-observer-enabled", value_name = "BOOL")]
+	pub observer_enabled: Option<bool>,
+
+	/// Never vote on a block closer than this to the best block. `0` disables the rule.
+	#[structopt(long = "grandpa-before-best-block-by", value_name = "BLOCKS")]
+	pub before_best_block_by: Option<u32>,
+
+	/// Enable or disable voting on at most three quarters of the unfinalized chain.
+	#[structopt(long = "grandpa-three-quarters-rule", value_name = "BOOL")]
+	pub three_quarters_of_unfinalized_chain: Option<bool>,
+}
+
+impl GrandpaParams {
+	/// Apply the overrides given on the command line on top of `params`.
+	pub fn apply(&self, params: &mut GrandpaParameters) {
+		if let Some(gossip_duration) = self.gossip_duration {
+			params.gossip_duration_millis = gossip_duration;
+		}
+		if let Some(justification_period) = self.justification_period {
+			params.justification_period = justification_period;
+		}
+		if let Some(observer_enabled) = self.observer_enabled {
+			params.observer_enabled = observer_enabled;
+		}
+		if let Some(before_best_block_by) = self.before_best_block_by {
+			params.before_best_block_by = before_best_block_by;
+		}
+		if let Some(three_quarters) = self.three_quarters_of_unfinalized_chain {
+			params.three_quarters_of_unfinalized_chain = three_quarters;
+		}
+	}
+}
+
+/// Aura authoring backoff overrides. Anything left unset is taken from the chain spec.
+#[derive(Debug, StructOpt)]
+pub struct AuthoringBackoffParams {
+	/// Keep authoring at full speed regardless of finality lag.
+	#[structopt(long = "no-authoring-backoff", conflicts_with = "enabled")]
+	pub disabled: bool,
+
+	/// Back off authoring while finality lags, even if the chain spec doesn't enable it.
+	#[structopt(long = "authoring-backoff")]
+	pub enabled: bool,
+
+	/// Maximum number of slots to skip between authored blocks.
+	#[structopt(long = "backoff-max-interval", value_name = "SLOTS")]
+	pub max_interval: Option<u32>,
+
+	/// Number of unfinalized blocks tolerated before backing off.
+	#[structopt(long = "backoff-unfinalized-slack", value_name = "BLOCKS")]
+	pub unfinalized_slack: Option<u32>,
+
+	/// Scales the backoff; higher values back off more slowly.
+	#[structopt(long = "backoff-authoring-bias", value_name = "BIAS")]
+	pub authoring_bias: Option<u32>,
+}
+
+impl AuthoringBackoffParams {
+	/// Apply the overrides given on the command line on top of `params`.
+	pub fn apply(
+		&self,
+		params: Option<AuthoringBackoffParameters>,
+	) -> Option<AuthoringBackoffParameters> {
+		if self.disabled {
+			return None;
+		}
+
+		let mut params = match params {
+			Some(params) => params,
+			None if self.enabled => Default::default(),
+			None => return None,
+		};
+
+		if let Some(max_interval) = self.max_interval {
+			params.max_interval = max_interval;
+		}
+		if let Some(unfinalized_slack) = self.unfinalized_slack {
+			params.unfinalized_slack = unfinalized_slack;
+		}
+		if let Some(authoring_bias) = self.authoring_bias {
+			params.authoring_bias = authoring_bias;
+		}
+
+		Some(params)
+	}
+}
+
+/// Offchain worker settings, written to offchain storage before the workers start.
+#[derive(Debug, StructOpt)]
+pub struct OcwParams {
+	/// TOML file with the offchain storage values of the offchain worker.
+	#[structopt(long = "ocw-config", value_name = "PATH", parse(from_os_str))]
+	pub config: Option<PathBuf>,
+
+	/// File whose content is written to offchain storage under a key, e.g. for API keys.
+	/// Overrides the config file. Can be given multiple times.
+	#[structopt(
+		long = "ocw-storage-file",
+		value_name = "KEY=PATH",
+		parse(try_from_str = parse_storage_file),
+	)]
+	pub storage_files: Vec<(String, PathBuf)>,
+
+	/// Drive the offchain workers with outgoing HTTP requests answered from the JSON
+	/// fixtures in this directory, with a deterministic clock and random seed. For local runs.
+	#[structopt(long = "offchain-http-mock", value_name = "DIR", parse(from_os_str))]
+	pub http_mock: Option<PathBuf>,
+}
+
+impl OcwParams {
+	/// Merge the config file with the command line and load all secrets.
+	pub fn config(&self) -> Result<Option<OcwConfig>, String> {
+		if self.config.is_none() && self.storage_files.is_empty() {
+			return Ok(None);
+		}
+
+		let mut file = match &self.config {
+			Some(path) => OcwConfigFile::load(path)?,
+			None => Default::default(),
+		};
+		file.storage_files.extend(self.storage_files.iter().cloned());
+
+		OcwConfig::from_file(file).map(Some)
+	}
+
+	/// Load the fixtures of `--offchain-http-mock`, if given.
+	pub fn http_fixtures(&self) -> Result<Option<HttpFixtures>, String> {
+		self.http_mock.as_deref().map(HttpFixtures::load).transpose()
+	}
+}
+
+/// Health and readiness HTTP endpoint.
+#[derive(Debug, StructOpt)]
+pub struct HealthParams {
+	/// Serve `/health` and `/ready` on this address, e.g. `127.0.0.1:9955`.
+	#[structopt(long = "health-endpoint", value_name = "ADDR")]
+	pub listen: Option<SocketAddr>,
+
+	/// Fewest peers a healthy node has.
+	#[structopt(long = "health-min-peers", value_name = "COUNT", default_value = "1")]
+	pub min_peers: usize,
+
+	/// Longest time without a new best block for a ready node.
+	#[structopt(long = "ready-max-best-age", value_name = "SECS", default_value = "60")]
+	pub max_best_age: u64,
+
+	/// Largest distance between the best and the finalized block for a ready node.
+	#[structopt(long = "ready-max-finality-lag", value_name = "BLOCKS", default_value = "20")]
+	pub max_finality_lag: u32,
+
+	/// Longest an offchain worker run may take before the node isn't ready.
+	#[structopt(long = "ready-max-ocw-runtime", value_name = "SECS", default_value = "120")]
+	pub max_ocw_runtime: u64,
+}
+
+impl HealthParams {
+	/// The endpoint configuration, if `--health-endpoint` is given.
+	pub fn config(&self) -> Option<HealthConfig> {
+		self.listen.map(|listen| HealthConfig {
+			listen,
+			min_peers: self.min_peers,
+			max_best_age: Duration::from_secs(self.max_best_age),
+			max_finality_lag: self.max_finality_lag,
+			max_ocw_runtime: Duration::from_secs(self.max_ocw_runtime),
+		})
+	}
+}
+
+/// `faucet_drip` RPC method, for development and local chains.
+#[derive(Debug, StructOpt)]
+pub struct FaucetParams {
+	/// Serve `faucet_drip` from this `acco` sr25519 key in the keystore, given as SS58 or hex.
+	#[structopt(long = "faucet-account", value_name = "PUBLIC")]
+	pub account: Option<String>,
+
+	/// Amount each drip transfers.
+	#[structopt(long = "faucet-amount", value_name = "BALANCE", default_value = "10000000000000")]
+	pub amount: Balance,
+
+	/// Shortest time between two drips to the same account.
+	#[structopt(long = "faucet-interval", value_name = "SECS", default_value = "3600")]
+	pub interval: u64,
+
+	/// Shortest time between two drips requested from the same IP on `--faucet-endpoint`.
+	#[structopt(long = "faucet-ip-interval", value_name = "SECS", default_value = "3600")]
+	pub ip_interval: u64,
+
+	/// Also serve `faucet_drip` as JSON-RPC over HTTP on this address, with drips
+	/// limited per IP.
+	#[structopt(long = "faucet-endpoint", value_name = "ADDR")]
+	pub listen: Option<SocketAddr>,
+
+	/// Allow `--faucet-endpoint` on an address other than a loopback one.
+	#[structopt(long = "unsafe-faucet-external")]
+	pub unsafe_external: bool,
+
+	/// Most the faucet transfers per UTC day.
+	#[structopt(long = "faucet-daily-cap", value_name = "BALANCE", default_value = "1000000000000000")]
+	pub daily_cap: Balance,
+}
+
+impl FaucetParams {
+	/// The faucet configuration, if `--faucet-account` is given. Fails on live chains.
+	pub fn config(&self, chain_type: ChainType) -> Result<Option<FaucetConfig>, String> {
+		let account = match &self.account {
+			Some(account) => account,
+			None => return Ok(None),
+		};
+		if !matches!(chain_type, ChainType::Development | ChainType::Local) {
+			return Err("`--faucet-account` is only allowed on development and local chains".into());
+		}
+
+		Ok(Some(FaucetConfig {
+			account: parse_public::<sr25519::Public>(account).map_err(|e| e.to_string())?,
+			amount: self.amount,
+			interval: Duration::from_secs(self.interval),
+			ip_interval: Duration::from_secs(self.ip_interval),
+			daily_cap: self.daily_cap,
+		}))
+	}
+
+	/// The faucet endpoint, if `--faucet-endpoint` is given.
+	pub fn endpoint(&self) -> Result<Option<FaucetEndpointConfig>, String> {
+		match (self.listen, &self.account) {
+			(Some(_), None) => Err("`--faucet-endpoint` requires `--faucet-account`".into()),
+			(listen, _) => Ok(listen.map(|listen| FaucetEndpointConfig { listen, external: self.unsafe_external })),
+		}
+	}
+}
+
+fn parse_storage_file(s: &str) -> Result<(String, PathBuf), String> {
+	let mut parts = s.splitn(2, '=');
+	match (parts.next(), parts.next()) {
+		(Some(key), Some(path)) if !key.is_empty() && !path.is_empty() =>
+			Ok((key.to_string(), PathBuf::from(path))),
+		_ => Err(format!("Expected KEY=PATH, got `{}`", s)),
+	}
+}
+
 #[derive(Debug, StructOpt)]
 pub enum Subcommand {
 	/// Key management cli utilities
-	Key(sc_cli::KeySubcommand),
+	Key(crate::key::KeySubcommand),
 
 	/// Build a chain specification.
 	BuildSpec(sc_cli::BuildSpecCmd),
@@ -36,6 +326,39 @@ pub enum Subcommand {
 	/// Revert the chain to a previous state.
 	Revert(sc_cli::RevertCmd),
 
+	/// Run the offchain worker for one block and print what it would do.
+	OcwSimulate(crate::ocw_simulate::OcwSimulateCmd),
+
+	/// Serve the token server API and the offchain worker's balance APIs from a fixture.
+	TokenServerMock(crate::token_server_mock::TokenServerMockCmd),
+
+	/// Sign the message `link_eth` expects with an Ethereum key, or verify such a signature.
+	LinkEthSign(crate::link_eth::LinkEthSignCmd),
+
+	/// Build, sign and submit transactions offline.
+	Tx(crate::tx::TxSubcommand),
+
+	/// Print an encoded extrinsic, events or a storage key as JSON.
+	Decode(crate::decode::DecodeCmd),
+
+	/// Print a block or extrinsic of the local database with its events and fees.
+	Inspect(crate::inspect::InspectCmd),
+
+	/// Write the runtime's custom types as a polkadot.js types bundle.
+	ExportTypes(crate::export_types::ExportTypesCmd),
+
+	/// Check that a runtime upgrade keeps transactions and storage compatible.
+	CheckUpgrade(crate::check_upgrade::CheckUpgradeCmd),
+
+	/// Check that the runtime code on chain is a given wasm blob.
+	VerifyRuntime(crate::verify_runtime::VerifyRuntimeCmd),
+
+	/// Write a raw chain spec for a local testnet from the state of a finalized block.
+	ForkOff(crate::fork_off::ForkOffCmd),
+
+	/// Index the events of finalized blocks into a SQLite database.
+	Index(crate::index::IndexCmd),
+
 	/// The custom benchmark subcommmand benchmarking runtime pallets.
 	#[structopt(name = "benchmark", about = "Benchmark runtime pallets.")]
 	Benchmark(frame_benchmarking_cli::BenchmarkCmd),
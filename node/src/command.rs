@@ -116,6 +116,56 @@ pub fn run() -> sc_cli::Result<()> {
 				Ok((cmd.run(client, backend), task_manager))
 			})
 		},
+		Some(Subcommand::OcwSimulate(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.async_run(|config| {
+				let PartialComponents { client, task_manager, backend, keystore_container, ..}
+					= service::new_partial(&config)?;
+				let keystore = keystore_container.sync_keystore();
+				Ok((async move { cmd.run(client, backend, keystore) }, task_manager))
+			})
+		},
+		Some(Subcommand::TokenServerMock(cmd)) => cmd.run(),
+		Some(Subcommand::LinkEthSign(cmd)) => cmd.run(),
+		Some(Subcommand::Tx(cmd)) => cmd.run(),
+		Some(Subcommand::Decode(cmd)) => cmd.run(),
+		Some(Subcommand::ExportTypes(cmd)) => cmd.run(),
+		Some(Subcommand::CheckUpgrade(cmd)) => cmd.run(),
+		Some(Subcommand::ForkOff(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.async_run(|config| {
+				let PartialComponents { client, task_manager, ..}
+					= service::new_partial(&config)?;
+				Ok((async move { cmd.run(client, config.chain_spec) }, task_manager))
+			})
+		},
+		Some(Subcommand::VerifyRuntime(cmd)) => match &cmd.snapshot {
+			Some(snapshot) => cmd.run_snapshot(snapshot),
+			None => {
+				let runner = cli.create_runner(cmd)?;
+				runner.async_run(|config| {
+					let PartialComponents { client, task_manager, backend, ..}
+						= service::new_partial(&config)?;
+					Ok((async move { cmd.run(client, backend) }, task_manager))
+				})
+			},
+		},
+		Some(Subcommand::Inspect(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.async_run(|config| {
+				let PartialComponents { client, task_manager, backend, ..}
+					= service::new_partial(&config)?;
+				Ok((async move { cmd.run(client, backend) }, task_manager))
+			})
+		},
+		Some(Subcommand::Index(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.async_run(|config| {
+				let PartialComponents { client, task_manager, backend, ..}
+					= service::new_partial(&config)?;
+				Ok((async move { cmd.run(client, backend) }, task_manager))
+			})
+		},
 		Some(Subcommand::Benchmark(cmd)) => {
 			if cfg!(feature = "runtime-benchmarks") {
 				let runner = cli.create_runner(cmd)?;
@@ -127,11 +177,40 @@ pub fn run() -> sc_cli::Result<()> {
 			}
 		},
 		None => {
-			let runner = cli.create_runner(&cli.run)?;
+			let runner = cli.create_runner(&cli.run.base)?;
+			let run = &cli.run;
 			runner.run_node_until_exit(|config| async move {
+				let mut grandpa = sc_chain_spec::get_extension::<chain_spec::GrandpaParameters>(
+					config.chain_spec.extensions(),
+				).cloned().unwrap_or_default();
+				run.grandpa.apply(&mut grandpa);
+				let authoring_backoff = run.backoff.apply(
+					sc_chain_spec::get_extension::<Option<chain_spec::AuthoringBackoffParameters>>(
+						config.chain_spec.extensions(),
+					).cloned().flatten(),
+				);
+
+				let options = service::NodeOptions {
+					grandpa,
+					authoring_backoff,
+					ocw_config: run.ocw.config()?,
+					http_fixtures: run.ocw.http_fixtures()?,
+					health: run.health.config(),
+					index_events: run.index_events.clone(),
+					token_server_probe: run.token_server_probe.clone(),
+					faucet: run.faucet.config(config.chain_spec.chain_type())?,
+					faucet_endpoint: run.faucet.endpoint()?,
+				};
+
 				match config.role {
+					Role::Light if options.ocw_config.is_some() || options.http_fixtures.is_some() => Err(sc_service::Error::Other(
+						"Light clients don't run offchain workers, offchain worker settings aren't allowed".into()
+					)),
+					Role::Light if options.faucet.is_some() => Err(sc_service::Error::Other(
+						"Light clients can't submit faucet transfers, `--faucet-account` isn't allowed".into()
+					)),
 					Role::Light => service::new_light(config),
-					_ => service::new_full(config),
+					_ => service::new_full(config, options),
 				}.map_err(sc_cli::Error::Service)
 			})
 		}
@@ -0,0 +1,54 @@
+//! `decode`: print SCALE encoded extrinsics, events and storage keys as JSON,
+//! using the metadata of the runtime compiled into the node.
+
+use structopt::{clap::ArgGroup, StructOpt};
+use sc_cli::Error;
+
+use crate::scale::Metadata;
+
+#[derive(Debug, StructOpt)]
+#[structopt(group = ArgGroup::with_name("input").required(true))]
+pub struct DecodeCmd {
+	/// An encoded extrinsic, as passed to `author_submitExtrinsic` or found in a block body.
+	#[structopt(long, value_name = "HEX", group = "input")]
+	pub extrinsic: Option<String>,
+
+	/// The encoded value of `System::Events`.
+	#[structopt(long, value_name = "HEX", group = "input")]
+	pub events: Option<String>,
+
+	/// A storage key, e.g. from `state_getKeys`.
+	#[structopt(long, value_name = "HEX", group = "input")]
+	pub storage_key: Option<String>,
+
+	/// The value stored under `--storage-key`, to decode with it.
+	#[structopt(long, value_name = "HEX", requires = "storage-key")]
+	pub value: Option<String>,
+}
+
+impl DecodeCmd {
+	/// Run the command.
+	pub fn run(&self) -> Result<(), Error> {
+		let metadata = Metadata::native()?;
+
+		let decoded = match (&self.extrinsic, &self.events, &self.storage_key) {
+			(Some(extrinsic), ..) => metadata.decode_extrinsic(&parse_hex(extrinsic)?)?,
+			(_, Some(events), _) => metadata.decode_events(&parse_hex(events)?)?,
+			(.., Some(key)) => {
+				let (entry, mut decoded) = metadata.decode_storage_key(&parse_hex(key)?)?;
+				if let Some(value) = &self.value {
+					decoded["value"] = metadata.decode(entry.ty.value(), &parse_hex(value)?)?;
+				}
+				decoded
+			},
+			_ => return Err("One of `--extrinsic`, `--events` or `--storage-key` is required".into()),
+		};
+
+		println!("{}", serde_json::to_string_pretty(&decoded).map_err(|e| e.to_string())?);
+		Ok(())
+	}
+}
+
+fn parse_hex(s: &str) -> Result<Vec<u8>, String> {
+	hex::decode(s.trim().trim_start_matches("0x")).map_err(|e| format!("Invalid hex: {}", e))
+}
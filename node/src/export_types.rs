@@ -0,0 +1,432 @@
+//! `export-types`: write the runtime's custom types as a polkadot.js types bundle.
+//!
+//! The bundle keeps one set of definitions per range of `spec_version`s. An
+//! export with a new `spec_version` closes the range of the previous set if the
+//! definitions changed, so clients can still decode old blocks. With `--check`
+//! nothing is written and the command fails if the file is out of date, which
+//! CI uses to keep `ts-tests/types.ts` in sync with the runtime.
+//!
+//! The definitions are those of [`TYPES`] that the runtime metadata uses and
+//! polkadot.js doesn't know. `--check` also fails if the metadata uses a type
+//! that neither defines, since clients couldn't decode it.
+
+use std::{collections::{BTreeMap, BTreeSet}, fmt, fs, marker::PhantomData, path::{Path, PathBuf}};
+use serde::{
+	de::{MapAccess, Visitor},
+	ser::SerializeMap,
+	Deserialize, Deserializer, Serialize, Serializer,
+};
+use structopt::StructOpt;
+use sc_cli::Error;
+use litentry_runtime::VERSION;
+
+use crate::scale::{self, Metadata, Ty, TypeDef, TYPES};
+
+/// Types polkadot.js defines itself, for Substrate and the FRAME pallets of this
+/// runtime. The metadata may use them without a definition in [`TYPES`], and
+/// those in [`TYPES`] aren't exported so the bundle can't diverge from upstream.
+const POLKADOT_JS_TYPES: &[&str] = &[
+	// Primitives and FRAME support
+	"Null", "Balance", "BalanceOf", "BlockNumber", "Index", "Moment", "Weight", "Perbill", "Permill",
+	"Percent", "Key", "StorageKey", "StorageData", "KeyValue", "Priority", "Period", "TaskAddress",
+	"CallHash", "CallHashOf", "MultiSignature", "Phase", "DispatchClass", "Pays", "DispatchInfo",
+	"DispatchError", "DispatchErrorModule", "DispatchResult", "BalanceStatus", "Status", "Event",
+	"EventRecord", "EventIndex", "PalletsOrigin", "Releases", "Multiplier", "Slot",
+	// System
+	"AccountInfo", "AccountData", "ConsumedWeight", "DigestOf", "LastRuntimeUpgradeInfo",
+	"ChangesTrieConfiguration",
+	// Balances
+	"BalanceLock",
+	// Aura and Grandpa
+	"AuthorityId", "AuthorityWeight", "AuthorityList", "EquivocationProof", "KeyOwnerProof",
+	"StoredState", "StoredPendingChange", "SetId", "SessionIndex",
+	// Treasury, Scheduler and Sudo
+	"Proposal", "Scheduled",
+	// Democracy and collectives
+	"ProposalIndex", "ReferendumIndex", "PropIndex", "MemberCount", "Vote", "Conviction", "AccountVote",
+	"AccountVoteStandard", "AccountVoteSplit", "VoteThreshold", "PreimageStatus", "ReferendumInfo",
+	"Voting", "Votes",
+	// Proxy, Recovery and Identity
+	"Timepoint", "ProxyDefinition", "Announcement", "RecoveryConfig", "ActiveRecovery", "RegistrarIndex",
+	"IdentityInfo", "IdentityFields", "Data", "Judgement", "Registration", "RegistrarInfo",
+];
+
+/// Types of the extrinsic format, which the metadata doesn't describe.
+const EXTRINSIC_TYPES: &[&str] = &["Address", "MultiSignature", "Compact<Index>", "Compact<Balance>"];
+
+/// Header and footer of a bundle written as TypeScript.
+const TS_HEADER: &str = "// Generated by `litentry-node export-types`. Do not edit.\n\nexport const typesBundle = ";
+const TS_FOOTER: &str = ";\n\nexport default typesBundle;\n";
+
+/// A JSON object whose keys keep their order, as struct fields must for polkadot.js.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ordered<V>(pub Vec<(String, V)>);
+
+impl<V: Serialize> Serialize for Ordered<V> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut map = serializer.serialize_map(Some(self.0.len()))?;
+		for (key, value) in &self.0 {
+			map.serialize_entry(key, value)?;
+		}
+		map.end()
+	}
+}
+
+impl<'de, V: Deserialize<'de>> Deserialize<'de> for Ordered<V> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		struct OrderedVisitor<V>(PhantomData<V>);
+
+		impl<'de, V: Deserialize<'de>> Visitor<'de> for OrderedVisitor<V> {
+			type Value = Ordered<V>;
+
+			fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+				formatter.write_str("an object")
+			}
+
+			fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+				let mut entries = Vec::new();
+				while let Some(entry) = map.next_entry()? {
+					entries.push(entry);
+				}
+				Ok(Ordered(entries))
+			}
+		}
+
+		deserializer.deserialize_map(OrderedVisitor(PhantomData))
+	}
+}
+
+/// Variants of an enum definition.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Variants {
+	/// Variants without payload, by name.
+	Unit(Vec<String>),
+	/// Variant names and their payload types, `Null` for none.
+	Payload(Ordered<String>),
+}
+
+/// A type definition in polkadot.js format.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Definition {
+	Alias(String),
+	Enum {
+		#[serde(rename = "_enum")]
+		variants: Variants,
+	},
+	Struct(Ordered<String>),
+}
+
+impl From<&TypeDef> for Definition {
+	fn from(def: &TypeDef) -> Self {
+		match def {
+			TypeDef::Alias(alias) => Definition::Alias(alias.to_string()),
+			TypeDef::Struct(fields) => Definition::Struct(Ordered(
+				fields.iter().map(|(name, ty)| (name.to_string(), ty.to_string())).collect(),
+			)),
+			TypeDef::Enum(variants) if variants.iter().all(|(_, payload)| payload.is_none()) =>
+				Definition::Enum {
+					variants: Variants::Unit(variants.iter().map(|(name, _)| name.to_string()).collect()),
+				},
+			TypeDef::Enum(variants) => Definition::Enum {
+				variants: Variants::Payload(Ordered(
+					variants.iter()
+						.map(|(name, payload)| (name.to_string(), payload.unwrap_or("Null").to_string()))
+						.collect(),
+				)),
+			},
+		}
+	}
+}
+
+/// Definitions valid for the `spec_version`s in `minmax`, both ends inclusive.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VersionedTypes {
+	pub minmax: (u32, Option<u32>),
+	pub types: Ordered<Definition>,
+}
+
+/// Definitions of one chain, by `spec_version`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SpecTypes {
+	pub types: Vec<VersionedTypes>,
+}
+
+/// A polkadot.js `typesBundle`, keyed by `spec_name`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TypesBundle {
+	pub spec: BTreeMap<String, SpecTypes>,
+}
+
+impl TypesBundle {
+	/// Parse a bundle written as JSON or as TypeScript by this command.
+	pub fn parse(content: &str) -> Result<Self, String> {
+		let json = match content.strip_prefix(TS_HEADER) {
+			Some(ts) => ts.strip_suffix(TS_FOOTER).ok_or("Unexpected end of the TypeScript bundle")?,
+			None => content,
+		};
+		serde_json::from_str(json).map_err(|e| format!("Invalid types bundle: {}", e))
+	}
+
+	/// Render the bundle as JSON, or as TypeScript if `ts` is set.
+	pub fn render(&self, ts: bool) -> Result<String, String> {
+		let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+		if ts {
+			Ok(format!("{}{}{}", TS_HEADER, json, TS_FOOTER))
+		} else {
+			Ok(format!("{}\n", json))
+		}
+	}
+
+	/// Record `types` as the definitions of `spec_name` from `spec_version` on.
+	pub fn update(&mut self, spec_name: &str, spec_version: u32, types: Ordered<Definition>) -> Result<(), String> {
+		let versions = &mut self.spec.entry(spec_name.to_string()).or_default().types;
+		match versions.last_mut() {
+			Some(last) if last.types == types && last.minmax.1.is_none() => return Ok(()),
+			Some(last) if last.minmax.0 > spec_version => return Err(format!(
+				"The bundle has types from spec version {}, newer than the runtime's {}",
+				last.minmax.0, spec_version,
+			)),
+			// Same spec version, e.g. during development: the types are just replaced.
+			Some(last) if last.minmax.0 == spec_version => {
+				last.types = types;
+				return Ok(());
+			},
+			Some(last) => last.minmax.1 = Some(spec_version - 1),
+			None => (),
+		}
+		versions.push(VersionedTypes { minmax: (spec_version, None), types });
+
+		Ok(())
+	}
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ExportTypesCmd {
+	/// Bundle to update. Written as TypeScript if it ends in `.ts`, as JSON otherwise.
+	/// Without it the bundle for the current runtime is printed as JSON.
+	#[structopt(long, short = "o", value_name = "PATH", parse(from_os_str))]
+	pub output: Option<PathBuf>,
+
+	/// Fail if `--output` doesn't match the runtime instead of updating it.
+	#[structopt(long, requires = "output")]
+	pub check: bool,
+}
+
+impl ExportTypesCmd {
+	/// Run the command.
+	pub fn run(&self) -> Result<(), Error> {
+		let metadata = Metadata::native()?;
+		let (types, unresolved) = metadata_types(&metadata);
+		if !unresolved.is_empty() {
+			let message = format!(
+				"The metadata uses types that neither the runtime nor polkadot.js define: {}. \
+				Add them to `TYPES` in `node/src/scale.rs`.",
+				unresolved.join(", "),
+			);
+			if self.check {
+				return Err(message.into());
+			}
+			eprintln!("Warning: {}", message);
+		}
+
+		let path = match &self.output {
+			Some(path) => path,
+			None => {
+				let mut bundle = TypesBundle::default();
+				bundle.update(&VERSION.spec_name, VERSION.spec_version, types)?;
+				print!("{}", bundle.render(false)?);
+				return Ok(());
+			},
+		};
+
+		let ts = path.extension().map_or(false, |extension| extension == "ts");
+		let current = read_if_exists(path)?;
+		let mut bundle = match &current {
+			Some(content) => TypesBundle::parse(content)
+				.map_err(|e| format!("{}: {}", path.display(), e))?,
+			None => Default::default(),
+		};
+		bundle.update(&VERSION.spec_name, VERSION.spec_version, types)?;
+		let rendered = bundle.render(ts)?;
+
+		if current.as_ref() == Some(&rendered) {
+			println!("{} is up to date", path.display());
+			return Ok(());
+		}
+		if self.check {
+			return Err(format!(
+				"{} doesn't match the runtime (spec version {}). Run `litentry-node export-types --output {}`.",
+				path.display(), VERSION.spec_version, path.display(),
+			).into());
+		}
+
+		fs::write(path, rendered)?;
+		println!("Wrote the types of spec version {} to {}", VERSION.spec_version, path.display());
+		Ok(())
+	}
+}
+
+fn read_if_exists(path: &Path) -> Result<Option<String>, Error> {
+	match fs::read_to_string(path) {
+		Ok(content) => Ok(Some(content)),
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+		Err(e) => Err(e.into()),
+	}
+}
+
+/// Type names of call arguments, event fields and storage in the metadata.
+fn metadata_type_names(metadata: &Metadata) -> Vec<String> {
+	let mut names = Vec::new();
+	for module in &metadata.modules {
+		names.extend(module.calls.iter().flatten().flat_map(|call| call.arguments.iter().map(|arg| arg.ty.clone())));
+		names.extend(module.event.iter().flatten().flat_map(|event| event.arguments.iter().cloned()));
+		for entry in module.storage.iter().flat_map(|storage| storage.entries.iter()) {
+			match &entry.ty {
+				scale::StorageEntryType::Plain(value) => names.push(value.clone()),
+				scale::StorageEntryType::Map { key, value, .. } => names.extend(vec![key.clone(), value.clone()]),
+				scale::StorageEntryType::DoubleMap { key1, key2, value, .. } =>
+					names.extend(vec![key1.clone(), key2.clone(), value.clone()]),
+			}
+		}
+	}
+	names
+}
+
+/// The definitions of [`TYPES`] that the metadata and the extrinsic format use,
+/// directly or through other definitions, except those polkadot.js has; and the
+/// names used that nothing defines.
+fn metadata_types(metadata: &Metadata) -> (Ordered<Definition>, Vec<String>) {
+	let mut walk = TypeWalk::default();
+	let names = metadata_type_names(metadata);
+	for name in EXTRINSIC_TYPES.iter().copied().chain(names.iter().map(String::as_str)) {
+		walk.type_str(name);
+	}
+
+	let types = TYPES.iter()
+		.filter(|(name, _)| walk.exported.contains(*name))
+		.map(|(name, def)| (name.to_string(), Definition::from(def)))
+		.collect();
+	(Ordered(types), walk.unresolved.into_iter().collect())
+}
+
+/// The named types reachable from some type strings.
+#[derive(Default)]
+struct TypeWalk {
+	seen: BTreeSet<String>,
+	exported: BTreeSet<String>,
+	unresolved: BTreeSet<String>,
+}
+
+impl TypeWalk {
+	fn type_str(&mut self, ty: &str) {
+		match scale::parse(ty) {
+			Ok(ty) => self.ty(&ty),
+			Err(_) => {
+				self.unresolved.insert(ty.to_string());
+			},
+		}
+	}
+
+	fn ty(&mut self, ty: &Ty) {
+		match ty {
+			Ty::Compact(inner) | Ty::Vec(inner) | Ty::Option(inner) | Ty::Array(inner, _) => self.ty(inner),
+			Ty::Tuple(types) => types.iter().for_each(|ty| self.ty(ty)),
+			Ty::Named(name) => self.named(name),
+			Ty::Generic(name, params) => {
+				self.named(name);
+				params.iter().for_each(|ty| self.ty(ty));
+			},
+			_ => (),
+		}
+	}
+
+	fn named(&mut self, name: &str) {
+		if !self.seen.insert(name.to_string()) || POLKADOT_JS_TYPES.contains(&name) {
+			return;
+		}
+		match scale::type_def(name) {
+			Some(def) => {
+				self.exported.insert(name.to_string());
+				for ty in def_types(def) {
+					self.type_str(ty);
+				}
+			},
+			None => {
+				self.unresolved.insert(name.to_string());
+			},
+		}
+	}
+}
+fn def_types(def: &TypeDef) -> Vec<&'static str> {
+	match def {
+		TypeDef::Alias(alias) => vec![*alias],
+		TypeDef::Struct(fields) => fields.iter().map(|(_, ty)| *ty).collect(),
+		TypeDef::Enum(variants) => variants.iter().filter_map(|(_, payload)| *payload).collect(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn types(alias: &str) -> Ordered<Definition> {
+		Ordered(vec![("Address".to_string(), Definition::Alias(alias.to_string()))])
+	}
+
+	fn ranges(bundle: &TypesBundle) -> Vec<(u32, Option<u32>)> {
+		bundle.spec["litentry"].types.iter().map(|versioned| versioned.minmax).collect()
+	}
+
+	#[test]
+	fn unchanged_types_keep_their_range() {
+		let mut bundle = TypesBundle::default();
+		bundle.update("litentry", 1, types("AccountId")).unwrap();
+		bundle.update("litentry", 3, types("AccountId")).unwrap();
+
+		assert_eq!(ranges(&bundle), vec![(1, None)]);
+	}
+
+	#[test]
+	fn changed_types_close_the_previous_range() {
+		let mut bundle = TypesBundle::default();
+		bundle.update("litentry", 1, types("AccountId")).unwrap();
+		bundle.update("litentry", 4, types("MultiAddress")).unwrap();
+
+		assert_eq!(ranges(&bundle), vec![(1, Some(3)), (4, None)]);
+		assert_eq!(bundle.spec["litentry"].types[0].types, types("AccountId"));
+		assert_eq!(bundle.spec["litentry"].types[1].types, types("MultiAddress"));
+	}
+
+	#[test]
+	fn same_spec_version_replaces_the_types() {
+		let mut bundle = TypesBundle::default();
+		bundle.update("litentry", 2, types("AccountId")).unwrap();
+		bundle.update("litentry", 2, types("MultiAddress")).unwrap();
+
+		assert_eq!(ranges(&bundle), vec![(2, None)]);
+		assert_eq!(bundle.spec["litentry"].types[0].types, types("MultiAddress"));
+	}
+
+	#[test]
+	fn older_spec_version_is_rejected() {
+		let mut bundle = TypesBundle::default();
+		bundle.update("litentry", 5, types("AccountId")).unwrap();
+		let before = bundle.clone();
+
+		assert!(bundle.update("litentry", 4, types("MultiAddress")).is_err());
+		assert_eq!(bundle, before);
+	}
+
+	#[test]
+	fn rendered_bundle_parses_back() {
+		let mut bundle = TypesBundle::default();
+		bundle.update("litentry", 1, types("AccountId")).unwrap();
+		bundle.update("litentry", 2, types("MultiAddress")).unwrap();
+
+		for ts in &[false, true] {
+			assert_eq!(TypesBundle::parse(&bundle.render(*ts).unwrap()), Ok(bundle.clone()));
+		}
+	}
+}
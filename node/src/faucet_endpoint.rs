@@ -0,0 +1,150 @@
+//! `--faucet-endpoint`: `faucet_drip` as JSON-RPC over HTTP on its own address.
+//!
+//! The node's RPC servers don't tell methods the caller's address, so this
+//! endpoint exists to limit drips per IP. It only listens on loopback addresses
+//! unless `--unsafe-faucet-external` is given.
+
+use std::{convert::Infallible, net::{IpAddr, SocketAddr}, sync::Arc};
+use futures::compat::Future01CompatExt;
+use hyper::{
+	server::{conn::{AddrIncoming, AddrStream}, Builder},
+	service::{make_service_fn, service_fn},
+	Body, Method, Request, Response, Server, StatusCode,
+};
+use jsonrpc_core::MetaIoHandler;
+use jsonrpc_derive::rpc;
+use litentry_runtime::{opaque::Block, AccountId, Hash, Index};
+use sp_api::{Core, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_transaction_pool::TransactionPool;
+use substrate_frame_rpc_system::AccountNonceApi;
+
+use crate::rpc::{faucet::Faucet, FutureResult};
+
+/// Where to serve the faucet endpoint.
+#[derive(Debug, Clone)]
+pub struct FaucetEndpointConfig {
+	/// Address to serve `faucet_drip` on.
+	pub listen: SocketAddr,
+	/// Whether `listen` may be other than a loopback address.
+	pub external: bool,
+}
+
+/// RPC metadata of the faucet endpoint.
+#[derive(Debug, Clone)]
+pub struct Caller {
+	/// Address of the peer that sent the request.
+	pub ip: IpAddr,
+}
+
+impl jsonrpc_core::Metadata for Caller {}
+
+/// `faucet_drip` with the caller's address.
+#[rpc(server)]
+pub trait FaucetEndpointApi<BlockHash> {
+	/// RPC metadata.
+	type Metadata;
+
+	/// Transfer the faucet amount to `account`. Returns the transaction hash.
+	#[rpc(meta, name = "faucet_drip")]
+	fn drip(&self, caller: Self::Metadata, account: AccountId) -> FutureResult<BlockHash>;
+}
+
+/// [`FaucetEndpointApi`] limiting drips per IP on top of the faucet's limits.
+pub struct FaucetEndpoint<C, P>(Faucet<C, P>);
+
+impl<C, P> FaucetEndpointApi<Hash> for FaucetEndpoint<C, P> where
+	C: HeaderBackend<Block> + ProvideRuntimeApi<Block> + Send + Sync + 'static,
+	C::Api: AccountNonceApi<Block, AccountId, Index> + Core<Block>,
+	P: TransactionPool<Block = Block, Hash = Hash> + 'static,
+{
+	type Metadata = Caller;
+
+	fn drip(&self, caller: Caller, account: AccountId) -> FutureResult<Hash> {
+		self.0.drip_from(Some(caller.ip), account)
+	}
+}
+
+/// Bind the faucet endpoint, refusing addresses other than loopback ones unless
+/// allowed. Done before the node starts so a taken address fails startup.
+pub fn bind(config: &FaucetEndpointConfig) -> Result<Builder<AddrIncoming>, String> {
+	let listen = &config.listen;
+	if !listen.ip().is_loopback() && !config.external {
+		return Err(format!(
+			"The faucet endpoint {} isn't a loopback address, pass `--unsafe-faucet-external` to serve it anyway",
+			listen,
+		));
+	}
+
+	Server::try_bind(listen)
+		.map_err(|e| format!("Failed to bind the faucet endpoint to {}: {}", listen, e))
+}
+
+async fn respond(
+	io: Arc<MetaIoHandler<Caller>>,
+	caller: Caller,
+	request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+	let status = |status| Response::builder()
+		.status(status)
+		.body(Body::empty())
+		.expect("static response parts are valid; qed");
+
+	if request.method() != Method::POST {
+		return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+	}
+	let body = match hyper::body::to_bytes(request.into_body()).await {
+		Ok(body) => body,
+		Err(_) => return Ok(status(StatusCode::BAD_REQUEST)),
+	};
+	let body = match std::str::from_utf8(&body) {
+		Ok(body) => body,
+		Err(_) => return Ok(status(StatusCode::BAD_REQUEST)),
+	};
+
+	let response = io.handle_request(body, caller).compat().await.ok().flatten();
+	Ok(Response::builder()
+		.status(StatusCode::OK)
+		.header("Content-Type", "application/json")
+		.body(Body::from(response.unwrap_or_default()))
+		.expect("static response parts are valid; qed"))
+}
+
+/// Serve `faucet_drip` from `faucet` until the node stops.
+pub async fn serve<C, P>(server: Builder<AddrIncoming>, faucet: Faucet<C, P>) where
+	C: HeaderBackend<Block> + ProvideRuntimeApi<Block> + Send + Sync + 'static,
+	C::Api: AccountNonceApi<Block, AccountId, Index> + Core<Block>,
+	P: TransactionPool<Block = Block, Hash = Hash> + 'static,
+{
+	let mut io = MetaIoHandler::default();
+	io.extend_with(FaucetEndpointApi::to_delegate(FaucetEndpoint(faucet)));
+	let io = Arc::new(io);
+
+	let service = make_service_fn(move |connection: &AddrStream| {
+		let io = io.clone();
+		let caller = Caller { ip: connection.remote_addr().ip() };
+		async move {
+			Ok::<_, Infallible>(service_fn(move |request| respond(io.clone(), caller.clone(), request)))
+		}
+	});
+
+	let server = server.serve(service);
+	log::info!("Faucet endpoint listening on http://{}", server.local_addr());
+	if let Err(e) = server.await {
+		log::error!("Faucet endpoint failed: {}", e);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn external_address_needs_the_unsafe_flag() {
+		for listen in &["0.0.0.0:9956", "[::]:9956", "192.168.1.2:9956"] {
+			let config = FaucetEndpointConfig { listen: listen.parse().unwrap(), external: false };
+			let refused = bind(&config).map(|_| ());
+			assert!(refused.unwrap_err().contains("--unsafe-faucet-external"));
+		}
+	}
+}
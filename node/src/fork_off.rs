@@ -0,0 +1,193 @@
+//! `fork-off`: turn the state of a finalized block into a raw chain spec for a
+//! local testnet, so upgrades and migrations can be rehearsed on real data.
+//!
+//! The state is exported like `export-state` does. Then the Aura and GRANDPA
+//! authorities and the sudo key are replaced by dev accounts, which are also
+//! endowed so they can pay fees, and `System` is reset so the state can be a
+//! genesis: block number 0, no block hashes or events.
+
+use std::{path::PathBuf, sync::Arc};
+use codec::{Decode, Encode};
+use serde_json::Value;
+use structopt::StructOpt;
+use sc_cli::{BlockNumberOrHash, CliConfiguration, Error, PruningParams, SharedParams};
+use sc_client_api::{Backend, StorageProvider, UsageProvider};
+use sc_service::ChainSpec;
+use sp_blockchain::HeaderBackend;
+use sp_core::{hashing::{blake2_128, twox_128}, sr25519, storage::Storage};
+use sp_consensus_aura::sr25519::AuthorityId as AuraId;
+use sp_finality_grandpa::{AuthorityId as GrandpaId, VersionedAuthorityList, GRANDPA_AUTHORITIES_KEY};
+use sp_runtime::{generic::BlockId, traits::Header as HeaderT};
+use litentry_runtime::{opaque::Block, AccountId, Balance, Index};
+
+use crate::chain_spec::{authority_keys_from_seed, get_account_id_from_seed};
+
+/// Balance given to each dev account, as in the dev chain's genesis.
+const ENDOWMENT: Balance = 1 << 60;
+
+type AccountInfo = frame_system::AccountInfo<Index, pallet_balances::AccountData<Balance>>;
+
+#[derive(Debug, StructOpt)]
+pub struct ForkOffCmd {
+	/// Finalized block hash or number to fork off at. Defaults to the last finalized block.
+	#[structopt(value_name = "HASH or NUMBER")]
+	pub block: Option<BlockNumberOrHash>,
+
+	/// Dev seed of an authority of the fork, e.g. `Alice`. May be given several times.
+	#[structopt(long = "authority", value_name = "SEED", default_value = "Alice")]
+	pub authorities: Vec<String>,
+
+	/// Dev seed of the sudo key of the fork.
+	#[structopt(long, value_name = "SEED", default_value = "Alice")]
+	pub sudo: String,
+
+	/// Write the chain spec to this file instead of stdout.
+	#[structopt(long, short = "o", value_name = "PATH", parse(from_os_str))]
+	pub output: Option<PathBuf>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub pruning_params: PruningParams,
+}
+
+impl ForkOffCmd {
+	/// Run the command.
+	pub fn run<C, B>(&self, client: Arc<C>, mut chain_spec: Box<dyn ChainSpec>) -> Result<(), Error> where
+		C: UsageProvider<Block> + StorageProvider<Block, B> + HeaderBackend<Block>,
+		B: Backend<Block>,
+	{
+		let info = client.info();
+		let at = match &self.block {
+			Some(block) => block.parse::<Block>()?,
+			None => BlockId::Hash(info.finalized_hash),
+		};
+		let header = client.header(at)?
+			.ok_or_else(|| format!("Block {:?} not found", at))?;
+		if *header.number() > info.finalized_number {
+			return Err(format!(
+				"Block #{} is not finalized yet, the last finalized block is #{}",
+				header.number(), info.finalized_number,
+			).into());
+		}
+
+		log::info!("Exporting the state at #{} ({})...", header.number(), header.hash());
+		let mut storage = sc_service::chain_ops::export_raw_state(client, &BlockId::Hash(header.hash()))?;
+		self.fork_storage(&mut storage)?;
+		chain_spec.set_storage(storage);
+
+		let json = sc_service::chain_ops::build_spec(&*chain_spec, true)?;
+		let mut spec: Value = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+		let fork = |field: &Value, suffix: &str| field.as_str().map(|value| Value::from(format!("{}{}", value, suffix)));
+		spec["name"] = fork(&spec["name"], " fork").unwrap_or_default();
+		spec["id"] = fork(&spec["id"], "_fork").unwrap_or_default();
+		spec["protocolId"] = fork(&spec["protocolId"], "-fork").unwrap_or_default();
+		spec["chainType"] = "Local".into();
+		spec["bootNodes"] = Value::Array(Vec::new());
+		spec["telemetryEndpoints"] = Value::Null;
+		// They name blocks of the original chain.
+		spec["forkBlocks"] = Value::Null;
+		spec["badBlocks"] = Value::Null;
+		let json = serde_json::to_string_pretty(&spec).map_err(|e| e.to_string())?;
+
+		match &self.output {
+			Some(path) => {
+				std::fs::write(path, json)?;
+				log::info!("Wrote the chain spec of the fork to {}", path.display());
+			},
+			None => println!("{}", json),
+		}
+		Ok(())
+	}
+
+	fn fork_storage(&self, storage: &mut Storage) -> Result<(), Error> {
+		let top = &mut storage.top;
+		let (aura, grandpa): (Vec<AuraId>, Vec<GrandpaId>) = self.authorities.iter()
+			.map(|seed| authority_keys_from_seed(seed))
+			.unzip();
+		let sudo = get_account_id_from_seed::<sr25519::Public>(&self.sudo);
+
+		top.insert(storage_key("Aura", "Authorities"), aura.encode());
+		remove_prefix(top, &twox_128(b"Grandpa"));
+		top.insert(
+			GRANDPA_AUTHORITIES_KEY.to_vec(),
+			VersionedAuthorityList::from(grandpa.into_iter().map(|id| (id, 1)).collect::<Vec<_>>()).encode(),
+		);
+		top.insert(storage_key("Sudo", "Key"), sudo.encode());
+
+		for item in &["Number", "BlockHash", "Events", "EventCount", "EventTopics", "Digest"] {
+			remove_prefix(top, &storage_key("System", item));
+		}
+		// What `frame_system` puts at genesis.
+		top.insert(
+			[storage_key("System", "BlockHash"), blake2_128_concat(&0u32.encode())].concat(),
+			[69u8; 32].encode(),
+		);
+		top.insert(storage_key("System", "ParentHash"), [69u8; 32].encode());
+
+		let mut endowed = self.authorities.iter()
+			.map(|seed| get_account_id_from_seed::<sr25519::Public>(seed))
+			.collect::<Vec<_>>();
+		endowed.push(sudo);
+		endowed.sort();
+		endowed.dedup();
+		for account in endowed {
+			endow(top, &account)?;
+		}
+
+		Ok(())
+	}
+}
+
+impl CliConfiguration for ForkOffCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+}
+
+fn storage_key(pallet: &str, item: &str) -> Vec<u8> {
+	[twox_128(pallet.as_bytes()), twox_128(item.as_bytes())].concat()
+}
+
+fn blake2_128_concat(key: &[u8]) -> Vec<u8> {
+	[&blake2_128(key)[..], key].concat()
+}
+
+fn remove_prefix(top: &mut std::collections::BTreeMap<Vec<u8>, Vec<u8>>, prefix: &[u8]) {
+	let keys = top.keys()
+		.filter(|key| key.starts_with(prefix))
+		.cloned()
+		.collect::<Vec<_>>();
+	for key in keys {
+		top.remove(&key);
+	}
+}
+
+/// Add [`ENDOWMENT`] to the free balance of `account` and the total issuance.
+fn endow(top: &mut std::collections::BTreeMap<Vec<u8>, Vec<u8>>, account: &AccountId) -> Result<(), Error> {
+	let key = [storage_key("System", "Account"), blake2_128_concat(account.as_ref())].concat();
+	let mut info = match top.get(&key) {
+		Some(value) => AccountInfo::decode(&mut &value[..])
+			.map_err(|e| format!("Invalid account info of {}: {}", account, e))?,
+		None => AccountInfo { providers: 1, ..Default::default() },
+	};
+	info.data.free = info.data.free.saturating_add(ENDOWMENT);
+	top.insert(key, info.encode());
+
+	let issuance_key = storage_key("Balances", "TotalIssuance");
+	let issuance = top.get(&issuance_key)
+		.map(|value| Balance::decode(&mut &value[..]))
+		.transpose()
+		.map_err(|e| format!("Invalid total issuance: {}", e))?
+		.unwrap_or_default();
+	top.insert(issuance_key, issuance.saturating_add(ENDOWMENT).encode());
+
+	Ok(())
+}
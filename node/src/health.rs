@@ -0,0 +1,186 @@
+//! Health and readiness HTTP endpoint for load balancers and orchestration.
+//!
+//! - `GET /health`: the node has enough peers and isn't major syncing.
+//! - `GET /ready`: healthy, and the best block is recent, finality keeps up and
+//!   no offchain worker run is stuck.
+//!
+//! Both answer `200` or `503` with a JSON body listing the problems found.
+
+use std::{
+	convert::Infallible,
+	net::SocketAddr,
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+use futures::{future, StreamExt};
+use hyper::{
+	server::{conn::AddrIncoming, Builder},
+	service::{make_service_fn, service_fn},
+	Body, Method, Request, Response, Server, StatusCode,
+};
+use serde_json::{json, Value};
+use sc_client_api::BlockchainEvents;
+use sc_network::NetworkService;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
+use litentry_runtime::opaque::Block;
+
+use crate::ocw::OcwActivity;
+
+/// Where to serve the endpoint and the thresholds it checks.
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+	/// Address to listen on.
+	pub listen: SocketAddr,
+	/// Fewest peers a healthy node has.
+	pub min_peers: usize,
+	/// Longest time without a new best block for a ready node.
+	pub max_best_age: Duration,
+	/// Largest distance between the best and the finalized block for a ready node.
+	pub max_finality_lag: u32,
+	/// Longest an offchain worker run may take before it counts as stuck.
+	pub max_ocw_runtime: Duration,
+}
+
+type Network = NetworkService<Block, <Block as BlockT>::Hash>;
+
+struct Checker<C> {
+	config: HealthConfig,
+	client: Arc<C>,
+	network: Arc<Network>,
+	ocw: Option<Arc<OcwActivity>>,
+	last_best: Mutex<Instant>,
+}
+
+impl<C: HeaderBackend<Block>> Checker<C> {
+	fn health(&self, problems: &mut Vec<String>) -> Value {
+		let peers = self.network.num_connected();
+		let is_major_syncing = self.network.is_major_syncing();
+
+		if peers < self.config.min_peers {
+			problems.push(format!("{} peers, expected at least {}", peers, self.config.min_peers));
+		}
+		if is_major_syncing {
+			problems.push("major syncing".into());
+		}
+
+		json!({ "peers": peers, "isMajorSyncing": is_major_syncing })
+	}
+
+	fn ready(&self, problems: &mut Vec<String>) -> Value {
+		let mut status = self.health(problems);
+		let info = self.client.info();
+		let best_age = self.last_best.lock().expect("health lock poisoned").elapsed();
+		let finality_lag = info.best_number.saturating_sub(info.finalized_number);
+
+		if best_age > self.config.max_best_age {
+			problems.push(format!("no new best block for {}s", best_age.as_secs()));
+		}
+		if finality_lag > self.config.max_finality_lag {
+			problems.push(format!(
+				"finalized block is {} blocks behind the best, at most {} allowed",
+				finality_lag, self.config.max_finality_lag,
+			));
+		}
+
+		status["best"] = info.best_number.into();
+		status["finalized"] = info.finalized_number.into();
+		status["bestAgeSecs"] = best_age.as_secs().into();
+
+		if let Some(ocw) = &self.ocw {
+			let longest_running = ocw.longest_running();
+			if let Some((number, running)) = longest_running {
+				if running > self.config.max_ocw_runtime {
+					problems.push(format!(
+						"offchain worker of block #{} running for {}s",
+						number, running.as_secs(),
+					));
+				}
+			}
+
+			status["offchainWorker"] = json!({
+				"longestRunning": longest_running.map(|(number, running)| json!({
+					"block": number,
+					"secs": running.as_secs(),
+				})),
+				"lastFinished": ocw.last_finished().map(|(number, since)| json!({
+					"block": number,
+					"secsAgo": since.as_secs(),
+				})),
+			});
+		}
+
+		status
+	}
+
+	fn respond(&self, request: &Request<Body>) -> Response<Body> {
+		let mut problems = Vec::new();
+		let mut status = match (request.method(), request.uri().path()) {
+			(&Method::GET, "/health") => self.health(&mut problems),
+			(&Method::GET, "/ready") => self.ready(&mut problems),
+			_ => return Response::builder()
+				.status(StatusCode::NOT_FOUND)
+				.body(Body::empty())
+				.expect("static response parts are valid; qed"),
+		};
+
+		let code = if problems.is_empty() { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+		status["status"] = if problems.is_empty() { "ok" } else { "unavailable" }.into();
+		status["problems"] = problems.into();
+
+		Response::builder()
+			.status(code)
+			.header("Content-Type", "application/json")
+			.body(Body::from(status.to_string()))
+			.expect("static response parts are valid; qed")
+	}
+}
+
+/// Bind the endpoint. Done before the node starts so a taken address fails startup.
+pub fn bind(config: &HealthConfig) -> Result<Builder<AddrIncoming>, String> {
+	Server::try_bind(&config.listen)
+		.map_err(|e| format!("Failed to bind the health endpoint to {}: {}", config.listen, e))
+}
+
+/// Serve the endpoint until the node stops.
+pub async fn serve<C>(
+	server: Builder<AddrIncoming>,
+	config: HealthConfig,
+	client: Arc<C>,
+	network: Arc<Network>,
+	ocw: Option<Arc<OcwActivity>>,
+) where
+	C: HeaderBackend<Block> + BlockchainEvents<Block> + Send + Sync + 'static,
+{
+	let mut notifications = client.import_notification_stream();
+	let listen = config.listen;
+	let checker = Arc::new(Checker { config, client, network, ocw, last_best: Mutex::new(Instant::now()) });
+
+	let track_best = {
+		let checker = checker.clone();
+		async move {
+			while let Some(notification) = notifications.next().await {
+				if notification.is_new_best {
+					*checker.last_best.lock().expect("health lock poisoned") = Instant::now();
+					log::trace!("Health: new best block #{}", notification.header.number());
+				}
+			}
+		}
+	};
+
+	let service = make_service_fn(move |_| {
+		let checker = checker.clone();
+		async move {
+			Ok::<_, Infallible>(service_fn(move |request| {
+				future::ok::<_, Infallible>(checker.respond(&request))
+			}))
+		}
+	});
+
+	log::info!("Health endpoint listening on http://{}", listen);
+	let server = server.serve(service);
+	futures::pin_mut!(track_best, server);
+	if let future::Either::Right((Err(e), _)) = future::select(track_best, server).await {
+		log::error!("Health endpoint failed: {}", e);
+	}
+}
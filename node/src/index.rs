@@ -0,0 +1,377 @@
+//! Index the events of finalized blocks into a SQLite database for analytics.
+//!
+//! `index` catches a database up with the finalized blocks of the local chain
+//! and exits, `--index-events` keeps it up to date while the node runs. Either
+//! resumes after the last indexed block, which [`SCHEMA`] keeps with the chain's
+//! genesis hash so a database isn't mixed up with another chain.
+//!
+//! Indexing reads the state of every block, so old blocks need an archive node
+//! (`--pruning archive`).
+//!
+//! Events that don't decode are stored encoded instead, see `events.raw`. If a
+//! block can't be indexed at all, `--index-events` logs the error and tries
+//! again with the next finalized block.
+
+use std::{path::{Path, PathBuf}, sync::Arc};
+use codec::{Decode, Encode};
+use futures::StreamExt;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::Value;
+use structopt::StructOpt;
+use sc_cli::{CliConfiguration, Error, ImportParams, SharedParams};
+use sc_client_api::{Backend, BlockBackend, BlockchainEvents, StateBackend};
+use sp_api::{Metadata as MetadataApi, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_core::{hashing::{blake2_256, twox_128}, storage::well_known_keys};
+use sp_runtime::{generic::BlockId, traits::Header as HeaderT};
+use litentry_runtime::{opaque::Block, Hash};
+
+use crate::scale::{self, Metadata};
+
+/// Tables of the index. Hashes are 0x-prefixed hex, accounts SS58 and
+/// arguments JSON formatted like `decode` prints them.
+pub const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS chain (
+	id INTEGER PRIMARY KEY CHECK (id = 0),
+	genesis_hash TEXT NOT NULL,
+	-- The last indexed block, all blocks up to it are indexed.
+	cursor_number INTEGER NOT NULL,
+	cursor_hash TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS blocks (
+	number INTEGER PRIMARY KEY,
+	hash TEXT NOT NULL UNIQUE,
+	parent_hash TEXT NOT NULL,
+	-- Milliseconds since the Unix epoch, from `Timestamp::Now`.
+	timestamp INTEGER
+);
+CREATE TABLE IF NOT EXISTS extrinsics (
+	block_number INTEGER NOT NULL REFERENCES blocks (number),
+	extrinsic_index INTEGER NOT NULL,
+	hash TEXT NOT NULL,
+	-- NULL for unsigned extrinsics.
+	signer TEXT,
+	-- NULL if the extrinsic couldn't be decoded.
+	pallet TEXT,
+	call TEXT,
+	args TEXT,
+	-- NULL if its events couldn't be decoded.
+	success INTEGER,
+	PRIMARY KEY (block_number, extrinsic_index)
+);
+CREATE INDEX IF NOT EXISTS extrinsics_by_signer ON extrinsics (signer);
+CREATE INDEX IF NOT EXISTS extrinsics_by_call ON extrinsics (pallet, call);
+CREATE TABLE IF NOT EXISTS events (
+	block_number INTEGER NOT NULL REFERENCES blocks (number),
+	event_index INTEGER NOT NULL,
+	-- NULL for events emitted outside of extrinsics.
+	extrinsic_index INTEGER,
+	-- NULL if the event couldn't be decoded that far.
+	pallet TEXT,
+	event TEXT,
+	-- NULL if the event couldn't be decoded.
+	args TEXT,
+	-- 'link', 'ocw', 'transfer', 'governance' or NULL.
+	category TEXT,
+	-- For an event that couldn't be decoded: the hex encoding of it and all
+	-- later events of the block, which can't be told apart, and why.
+	raw TEXT,
+	decode_error TEXT,
+	PRIMARY KEY (block_number, event_index)
+);
+CREATE INDEX IF NOT EXISTS events_by_name ON events (pallet, event);
+CREATE INDEX IF NOT EXISTS events_by_category ON events (category);
+-- The accounts among the arguments of each event.
+CREATE TABLE IF NOT EXISTS event_accounts (
+	account TEXT NOT NULL,
+	block_number INTEGER NOT NULL,
+	event_index INTEGER NOT NULL,
+	PRIMARY KEY (account, block_number, event_index)
+);
+";
+
+#[derive(Debug, StructOpt)]
+pub struct IndexCmd {
+	/// SQLite database to write to. Created if missing.
+	#[structopt(long, value_name = "PATH", parse(from_os_str))]
+	pub database: PathBuf,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub import_params: ImportParams,
+}
+
+impl IndexCmd {
+	/// Index the finalized blocks not indexed yet.
+	pub fn run<C, B>(&self, client: Arc<C>, backend: Arc<B>) -> Result<(), Error> where
+		C: HeaderBackend<Block> + BlockBackend<Block> + ProvideRuntimeApi<Block>,
+		C::Api: MetadataApi<Block>,
+		B: Backend<Block>,
+	{
+		let mut indexer = EventIndexer::open(client, backend, &self.database)?;
+		let indexed = indexer.catch_up()?;
+		println!("Indexed {} blocks into {}", indexed, self.database.display());
+		Ok(())
+	}
+}
+
+impl CliConfiguration for IndexCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn import_params(&self) -> Option<&ImportParams> {
+		Some(&self.import_params)
+	}
+}
+
+/// Writes the events of finalized blocks to the database.
+pub struct EventIndexer<C, B> {
+	client: Arc<C>,
+	backend: Arc<B>,
+	db: Connection,
+	/// The metadata of the runtime the last block was executed with, by code hash.
+	metadata: Option<(Option<Hash>, Metadata)>,
+}
+
+impl<C, B> EventIndexer<C, B> where
+	C: HeaderBackend<Block> + BlockBackend<Block> + ProvideRuntimeApi<Block>,
+	C::Api: MetadataApi<Block>,
+	B: Backend<Block>,
+{
+	/// Open the database at `path` and check it indexes this chain.
+	pub fn open(client: Arc<C>, backend: Arc<B>, path: &Path) -> Result<Self, String> {
+		let db = Connection::open(path)
+			.and_then(|db| db.execute_batch(SCHEMA).map(|()| db))
+			.map_err(|e| format!("Failed to open the index {}: {}", path.display(), e))?;
+
+		let genesis_hash = hex_hash(&client.info().genesis_hash);
+		let chain = db.query_row(
+			"SELECT genesis_hash, cursor_number, cursor_hash FROM chain",
+			params![],
+			|row| Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?, row.get::<_, String>(2)?)),
+		).optional().map_err(|e| format!("Failed to read the index cursor: {}", e))?;
+
+		if let Some((indexed_genesis, number, hash)) = chain {
+			if indexed_genesis != genesis_hash {
+				return Err(format!(
+					"{} indexes the chain with genesis {}, not {}",
+					path.display(), indexed_genesis, genesis_hash,
+				));
+			}
+			let local_hash = client.hash(number).map_err(|e| e.to_string())?.map(|hash| hex_hash(&hash));
+			if local_hash.as_ref() != Some(&hash) {
+				return Err(format!(
+					"{} indexed block #{} {}, which the local chain doesn't have",
+					path.display(), number, hash,
+				));
+			}
+			log::info!("Resuming the event index {} after #{}", path.display(), number);
+		}
+
+		Ok(EventIndexer { client, backend, db, metadata: None })
+	}
+
+	/// The last indexed block number.
+	fn cursor(&self) -> Result<Option<u32>, String> {
+		self.db.query_row("SELECT cursor_number FROM chain", params![], |row| row.get(0))
+			.optional()
+			.map_err(|e| format!("Failed to read the index cursor: {}", e))
+	}
+
+	/// Index the finalized blocks after the cursor. Returns how many were indexed.
+	pub fn catch_up(&mut self) -> Result<u32, String> {
+		let finalized = self.client.info().finalized_number;
+		let first = self.cursor()?.map_or(0, |cursor| cursor + 1);
+
+		for number in first..=finalized {
+			self.index_block(number)?;
+			if number % 1000 == 0 && number != finalized {
+				log::info!("Indexed events up to #{} of #{}", number, finalized);
+			}
+		}
+		Ok((finalized + 1).saturating_sub(first))
+	}
+
+	fn index_block(&mut self, number: u32) -> Result<(), String> {
+		let hash = self.client.hash(number).map_err(|e| e.to_string())?
+			.ok_or_else(|| format!("Block #{} not found", number))?;
+		let at = BlockId::Hash(hash);
+		let header = self.client.header(at).map_err(|e| e.to_string())?
+			.ok_or_else(|| format!("Header of block #{} not found", number))?;
+		let extrinsics = self.client.block_body(&at).map_err(|e| e.to_string())?
+			.ok_or_else(|| format!("Body of block #{} not found", number))?;
+
+		let state = self.backend.state_at(at)
+			.map_err(|e| format!("State of #{} unavailable, old blocks need an archive node: {:?}", number, e))?;
+		let read = |key: &[u8]| state.storage(key)
+			.map_err(|e| format!("Failed to read the state of #{}: {:?}", number, e));
+		let code_hash = state.storage_hash(well_known_keys::CODE)
+			.map_err(|e| format!("Failed to read the code hash of #{}: {:?}", number, e))?;
+		let timestamp = read(&[twox_128(b"Timestamp"), twox_128(b"Now")].concat())?
+			.map(|value| u64::decode(&mut &value[..]))
+			.transpose()
+			.map_err(|e| format!("Invalid timestamp of #{}: {}", number, e))?;
+		let events = read(&[twox_128(b"System"), twox_128(b"Events")].concat())?
+			.unwrap_or_else(|| vec![0]);
+
+		if self.metadata.as_ref().map(|(hash, _)| *hash) != Some(code_hash) {
+			let metadata = self.client.runtime_api().metadata(&at)
+				.map_err(|e| format!("Failed to get the metadata of #{}: {:?}", number, e))?;
+			self.metadata = Some((code_hash, Metadata::from_encoded(&metadata)?));
+		}
+		let metadata = &self.metadata.as_ref().expect("set above; qed").1;
+		let events = metadata.decode_events_lossy(&events)?;
+		if let Some(undecoded) = &events.undecoded {
+			log::warn!(
+				"Events {} to {} of #{} not decoded, indexed encoded: {}",
+				undecoded.index, undecoded.index + undecoded.count - 1, number, undecoded.error,
+			);
+		}
+
+		let sql_err = |e: rusqlite::Error| format!("Failed to index #{}: {}", number, e);
+		let tx = self.db.transaction().map_err(sql_err)?;
+		tx.execute(
+			"INSERT INTO blocks (number, hash, parent_hash, timestamp) VALUES (?1, ?2, ?3, ?4)",
+			params![number, hex_hash(&hash), hex_hash(header.parent_hash()), timestamp.map(|t| t as i64)],
+		).map_err(sql_err)?;
+
+		for (index, extrinsic) in extrinsics.iter().enumerate() {
+			let encoded = extrinsic.encode();
+			let decoded = metadata.decode_extrinsic(&encoded).unwrap_or_else(|e| {
+				log::warn!("Extrinsic {} of #{} not decoded: {}", index, number, e);
+				Value::Null
+			});
+			let outcome = events.decoded.iter().find(|event| {
+				event["phase"]["ApplyExtrinsic"] == Value::from(index)
+					&& event["pallet"] == "System"
+					&& (event["event"] == "ExtrinsicSuccess" || event["event"] == "ExtrinsicFailed")
+			});
+			let success = match outcome {
+				Some(event) => Some(event["event"] == "ExtrinsicSuccess"),
+				None if events.undecoded.is_some() => None,
+				None => Some(false),
+			};
+			tx.execute(
+				"INSERT INTO extrinsics (block_number, extrinsic_index, hash, signer, pallet, call, args, success)
+				VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+				params![
+					number,
+					index as u32,
+					format!("0x{}", hex::encode(blake2_256(&encoded))),
+					decoded["signer"].as_str(),
+					decoded["call"]["pallet"].as_str(),
+					decoded["call"]["call"].as_str(),
+					decoded.get("call").map(|call| call["args"].to_string()),
+					success,
+				],
+			).map_err(sql_err)?;
+		}
+
+		for (index, event) in events.decoded.iter().enumerate() {
+			let (pallet, name) = (event["pallet"].as_str().unwrap_or_default(), event["event"].as_str().unwrap_or_default());
+			tx.execute(
+				"INSERT INTO events (block_number, event_index, extrinsic_index, pallet, event, args, category)
+				VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+				params![
+					number,
+					index as u32,
+					event["phase"]["ApplyExtrinsic"].as_u64().map(|index| index as u32),
+					pallet,
+					name,
+					event["args"].to_string(),
+					category(pallet, name),
+				],
+			).map_err(sql_err)?;
+
+			for account in accounts(metadata, pallet, name, &event["args"]) {
+				tx.execute(
+					"INSERT OR IGNORE INTO event_accounts (account, block_number, event_index) VALUES (?1, ?2, ?3)",
+					params![account, number, index as u32],
+				).map_err(sql_err)?;
+			}
+		}
+
+		if let Some(undecoded) = events.undecoded {
+			let header = &undecoded.header;
+			let (pallet, name) = (header["pallet"].as_str(), header["event"].as_str());
+			tx.execute(
+				"INSERT INTO events (block_number, event_index, extrinsic_index, pallet, event, category, raw, decode_error)
+				VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+				params![
+					number,
+					undecoded.index as u32,
+					header["phase"]["ApplyExtrinsic"].as_u64().map(|index| index as u32),
+					pallet,
+					name,
+					pallet.and_then(|pallet| category(pallet, name.unwrap_or_default())),
+					format!("0x{}", hex::encode(&undecoded.raw)),
+					undecoded.error,
+				],
+			).map_err(sql_err)?;
+		}
+
+		tx.execute(
+			"INSERT INTO chain (id, genesis_hash, cursor_number, cursor_hash) VALUES (0, ?1, ?2, ?3)
+			ON CONFLICT (id) DO UPDATE SET cursor_number = excluded.cursor_number, cursor_hash = excluded.cursor_hash",
+			params![hex_hash(&self.client.info().genesis_hash), number, hex_hash(&hash)],
+		).map_err(sql_err)?;
+		tx.commit().map_err(sql_err)
+	}
+}
+
+impl<C, B> EventIndexer<C, B> where
+	C: HeaderBackend<Block> + BlockBackend<Block> + ProvideRuntimeApi<Block> + BlockchainEvents<Block>,
+	C::Api: MetadataApi<Block>,
+	B: Backend<Block>,
+{
+	/// Catch up, then index each newly finalized block until the node stops.
+	/// A block that fails is tried again with the next finalized block.
+	pub async fn follow(mut self) {
+		let mut notifications = self.client.finality_notification_stream();
+		if let Err(e) = self.catch_up() {
+			log::error!("Event indexing failed, retrying with the next finalized block: {}", e);
+		}
+
+		while let Some(notification) = notifications.next().await {
+			log::trace!("Indexing events up to #{}", notification.header.number());
+			if let Err(e) = self.catch_up() {
+				log::error!("Event indexing failed, retrying with the next finalized block: {}", e);
+			}
+		}
+	}
+}
+
+fn hex_hash(hash: &Hash) -> String {
+	format!("0x{}", hex::encode(hash))
+}
+
+/// The category of events the analytics queries look for.
+fn category(pallet: &str, event: &str) -> Option<&'static str> {
+	match (pallet, event) {
+		("AccountLinkerModule", _) => Some("link"),
+		("OffchainWorkerModule", _) => Some("ocw"),
+		("Balances", "Transfer") => Some("transfer"),
+		("Democracy", _) | ("Council", _) | ("TechnicalCommittee", _) | ("Treasury", _) | ("Sudo", _) =>
+			Some("governance"),
+		_ => None,
+	}
+}
+
+/// The `AccountId` arguments of the event `pallet::event`.
+fn accounts<'a>(metadata: &Metadata, pallet: &str, event: &str, args: &'a Value) -> Vec<&'a str> {
+	let types = metadata.module(pallet).ok()
+		.and_then(|module| module.event.iter().flatten().find(|meta| meta.name == event))
+		.map(|meta| meta.arguments.clone())
+		.unwrap_or_default();
+
+	types.iter()
+		.zip(args.as_array().into_iter().flatten())
+		.filter(|(ty, _)| scale::canonical(ty) == "AccountId")
+		.filter_map(|(_, arg)| arg.as_str())
+		.collect()
+}
@@ -0,0 +1,243 @@
+//! `inspect`: print the blocks and extrinsics of the local database as JSON,
+//! decoded with the metadata of the runtime they were executed with.
+//!
+//! Each extrinsic is shown with its dispatch result and weight from the
+//! `System::ExtrinsicSuccess`/`ExtrinsicFailed` event, the events it emitted
+//! and, for signed extrinsics, an estimated fee. The runtime burns fees without
+//! an event, so the estimate is `TransactionPaymentApi::query_info` at the
+//! parent block plus the tip, which doesn't account for a refund of unused
+//! weight after dispatch.
+//!
+//! Events that fail to decode are shown still encoded, with the error, see
+//! [`Metadata::decode_events_lossy`].
+
+use std::{str::FromStr, sync::Arc};
+use codec::Encode;
+use serde_json::{json, Value};
+use structopt::StructOpt;
+use sc_cli::{BlockNumberOrHash, CliConfiguration, Error, ImportParams, SharedParams};
+use sc_client_api::{Backend, BlockBackend, StateBackend};
+use sp_api::{Metadata as MetadataApi, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_core::hashing::{blake2_256, twox_128};
+use sp_runtime::{generic::BlockId, traits::{Block as BlockT, Header as HeaderT}};
+use pallet_transaction_payment_rpc_runtime_api::TransactionPaymentApi;
+use litentry_runtime::{opaque::Block, Balance};
+
+use crate::scale::Metadata;
+
+#[derive(Debug, StructOpt)]
+pub enum InspectSubcommand {
+	/// Decode a block and all of its extrinsics.
+	Block {
+		/// Block hash or number.
+		#[structopt(value_name = "HASH or NUMBER")]
+		input: BlockNumberOrHash,
+	},
+
+	/// Decode one extrinsic of a block.
+	Extrinsic {
+		/// The block and the index of the extrinsic in it.
+		#[structopt(value_name = "HASH or NUMBER:INDEX")]
+		input: ExtrinsicAddress,
+	},
+}
+
+/// An extrinsic, addressed as `<block>:<index>`.
+#[derive(Debug)]
+pub struct ExtrinsicAddress {
+	block: BlockNumberOrHash,
+	index: usize,
+}
+
+impl FromStr for ExtrinsicAddress {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let separator = s.rfind(':').ok_or_else(|| format!("Expected <block>:<index>, got `{}`", s))?;
+		let index = s[separator + 1..].parse()
+			.map_err(|_| format!("Invalid extrinsic index in `{}`", s))?;
+
+		Ok(ExtrinsicAddress { block: s[..separator].parse()?, index })
+	}
+}
+
+#[derive(Debug, StructOpt)]
+pub struct InspectCmd {
+	#[allow(missing_docs)]
+	#[structopt(subcommand)]
+	pub command: InspectSubcommand,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub import_params: ImportParams,
+}
+
+impl InspectCmd {
+	/// Run the command.
+	pub fn run<C, B>(&self, client: Arc<C>, backend: Arc<B>) -> Result<(), Error> where
+		C: HeaderBackend<Block> + BlockBackend<Block> + ProvideRuntimeApi<Block>,
+		C::Api: MetadataApi<Block> + TransactionPaymentApi<Block, Balance>,
+		B: Backend<Block>,
+	{
+		let (block, only) = match &self.command {
+			InspectSubcommand::Block { input } => (input, None),
+			InspectSubcommand::Extrinsic { input } => (&input.block, Some(input.index)),
+		};
+		let inspected = BlockInspector::new(&*client, &*backend, block.parse::<Block>()?)?
+			.inspect(only)?;
+
+		println!("{}", serde_json::to_string_pretty(&inspected).map_err(|e| e.to_string())?);
+		Ok(())
+	}
+}
+
+impl CliConfiguration for InspectCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn import_params(&self) -> Option<&ImportParams> {
+		Some(&self.import_params)
+	}
+}
+
+struct BlockInspector<'a, C> {
+	client: &'a C,
+	header: <Block as BlockT>::Header,
+	extrinsics: Vec<<Block as BlockT>::Extrinsic>,
+	metadata: Metadata,
+	events: Vec<Value>,
+}
+
+impl<'a, C> BlockInspector<'a, C> where
+	C: HeaderBackend<Block> + BlockBackend<Block> + ProvideRuntimeApi<Block>,
+	C::Api: MetadataApi<Block> + TransactionPaymentApi<Block, Balance>,
+{
+	fn new<B: Backend<Block>>(client: &'a C, backend: &B, at: BlockId<Block>) -> Result<Self, Error> {
+		let header = client.header(at)?
+			.ok_or_else(|| format!("Block {:?} not found", at))?;
+		let at = BlockId::Hash(header.hash());
+		let extrinsics = client.block_body(&at)?
+			.ok_or_else(|| format!("Body of block {:?} not found", at))?;
+
+		let metadata = client.runtime_api().metadata(&at)
+			.map_err(|e| format!("Failed to get the metadata at {:?}: {:?}", at, e))?;
+		let metadata = Metadata::from_encoded(&metadata)?;
+
+		let key = [twox_128(b"System"), twox_128(b"Events")].concat();
+		let events = backend.state_at(at)
+			.map_err(|e| format!("State of {:?} unavailable: {:?}", at, e))?
+			.storage(&key)
+			.map_err(|e| format!("Failed to read the events of {:?}: {:?}", at, e))?
+			.unwrap_or_else(|| vec![0]);
+		let events = metadata.decode_events_lossy(&events)?;
+		let events = events.decoded.into_iter().chain(events.undecoded.map(|undecoded| {
+			let mut event = undecoded.header;
+			event["raw"] = format!("0x{}", hex::encode(&undecoded.raw)).into();
+			event["count"] = undecoded.count.into();
+			event["decodeError"] = undecoded.error.into();
+			event
+		})).collect();
+
+		Ok(BlockInspector { client, header, extrinsics, metadata, events })
+	}
+
+	fn inspect(&self, only: Option<usize>) -> Result<Value, Error> {
+		let block = json!({
+			"number": self.header.number(),
+			"hash": self.header.hash(),
+			"parentHash": self.header.parent_hash(),
+			"stateRoot": self.header.state_root(),
+			"extrinsicsRoot": self.header.extrinsics_root(),
+		});
+
+		if let Some(index) = only {
+			if index >= self.extrinsics.len() {
+				return Err(format!(
+					"Block #{} has {} extrinsics, no index {}",
+					self.header.number(), self.extrinsics.len(), index,
+				).into());
+			}
+			return Ok(json!({ "block": block, "extrinsic": self.extrinsic(index)? }));
+		}
+
+		let extrinsics = (0..self.extrinsics.len())
+			.map(|index| self.extrinsic(index))
+			.collect::<Result<Vec<_>, _>>()?;
+		let other_events = self.events.iter()
+			.filter(|event| event["phase"].get("ApplyExtrinsic").is_none())
+			.collect::<Vec<_>>();
+
+		Ok(json!({ "block": block, "extrinsics": extrinsics, "events": other_events }))
+	}
+
+	fn extrinsic(&self, index: usize) -> Result<Value, Error> {
+		let extrinsic = &self.extrinsics[index];
+		let encoded = extrinsic.encode();
+		let mut inspected = json!({
+			"index": index,
+			"hash": format!("0x{}", hex::encode(blake2_256(&encoded))),
+			"length": encoded.len(),
+		});
+
+		let decoded = self.metadata.decode_extrinsic(&encoded);
+		let events = self.events.iter()
+			.filter(|event| event["phase"]["ApplyExtrinsic"] == json!(index))
+			.map(|event| {
+				let mut event = event.clone();
+				if let Some(event) = event.as_object_mut() {
+					event.remove("phase");
+					event.remove("topics");
+				}
+				event
+			})
+			.collect::<Vec<_>>();
+
+		let outcome = events.iter().find(|event| event["pallet"] == "System" && (
+			event["event"] == "ExtrinsicSuccess" || event["event"] == "ExtrinsicFailed"
+		));
+		if let Some(outcome) = outcome {
+			let (result, info) = match outcome["event"].as_str() {
+				Some("ExtrinsicSuccess") => (json!("Ok"), &outcome["args"][0]),
+				_ => (json!({ "Err": outcome["args"][0] }), &outcome["args"][1]),
+			};
+			inspected["result"] = result;
+			inspected["weight"] = info["weight"].clone();
+			inspected["class"] = info["class"].clone();
+			inspected["paysFee"] = info["paysFee"].clone();
+		}
+
+		match &decoded {
+			Ok(decoded) if decoded.get("signer").is_some() => {
+				inspected["estimatedFee"] = self.estimated_fee(extrinsic, encoded.len(), &decoded["tip"])?.into();
+			},
+			_ => (),
+		}
+		match decoded {
+			Ok(decoded) => inspected["extrinsic"] = decoded,
+			Err(e) => inspected["decodeError"] = e.into(),
+		}
+		inspected["events"] = events.into();
+
+		Ok(inspected)
+	}
+
+	/// Fee of a signed extrinsic as estimated before dispatch, i.e. without a refund.
+	fn estimated_fee(&self, extrinsic: &<Block as BlockT>::Extrinsic, len: usize, tip: &Value) -> Result<String, Error> {
+		let parent = BlockId::Hash(*self.header.parent_hash());
+		let info = self.client.runtime_api().query_info(&parent, extrinsic.clone(), len as u32)
+			.map_err(|e| format!("Failed to compute the fee at {:?}: {:?}", parent, e))?;
+		let tip = match tip {
+			Value::Number(tip) => tip.as_u64().map(Balance::from),
+			Value::String(tip) => tip.parse().ok(),
+			_ => None,
+		}.unwrap_or_default();
+
+		Ok(info.partial_fee.saturating_add(tip).to_string())
+	}
+}
@@ -0,0 +1,158 @@
+//! `key` subcommands: the upstream key utilities plus session key management
+//! directly against the node's keystore.
+
+use std::{path::PathBuf, sync::Arc};
+use codec::Encode;
+use structopt::StructOpt;
+use sc_cli::{Error, KeystoreParams, SharedParams, SubstrateCli};
+use sc_keystore::LocalKeystore;
+use sc_service::{config::{BasePath, KeystoreConfig}, ChainSpec};
+use sp_core::{
+	crypto::{key_types, KeyTypeId, Pair, Public},
+	ed25519, sr25519, hexdisplay::HexDisplay,
+};
+use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
+use litentry_runtime::opaque::SessionKeys;
+
+/// Key type the offchain worker signs its submissions with.
+pub const OCW_KEY_TYPE: KeyTypeId = KeyTypeId(*b"ocw!");
+
+#[derive(Debug, StructOpt)]
+pub enum KeySubcommand {
+	#[structopt(flatten)]
+	Base(sc_cli::KeySubcommand),
+
+	/// Insert the aura, grandpa and offchain worker keys derived from one secret into the keystore.
+	InsertSession(InsertSessionCmd),
+
+	/// Generate fresh aura, grandpa and offchain worker keys in the keystore.
+	Rotate(RotateCmd),
+}
+
+impl KeySubcommand {
+	/// Run the key subcommand.
+	pub fn run<C: SubstrateCli>(&self, cli: &C) -> Result<(), Error> {
+		match self {
+			KeySubcommand::Base(cmd) => cmd.run(cli),
+			KeySubcommand::InsertSession(cmd) => cmd.run(cli),
+			KeySubcommand::Rotate(cmd) => cmd.run(cli),
+		}
+	}
+}
+
+#[derive(Debug, StructOpt)]
+pub struct InsertSessionCmd {
+	/// Secret URI all session keys are derived from, e.g. `//Alice` or a mnemonic phrase.
+	#[structopt(long, conflicts_with = "secret-file", required_unless = "secret-file")]
+	pub suri: Option<String>,
+
+	/// File holding the secret URI or mnemonic phrase.
+	#[structopt(long, parse(from_os_str))]
+	pub secret_file: Option<PathBuf>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub keystore_params: KeystoreParams,
+}
+
+impl InsertSessionCmd {
+	/// Run the command.
+	pub fn run<C: SubstrateCli>(&self, cli: &C) -> Result<(), Error> {
+		let suri = match (&self.suri, &self.secret_file) {
+			(Some(suri), _) => suri.clone(),
+			(None, Some(path)) => std::fs::read_to_string(path)?.trim().to_string(),
+			(None, None) => return Err("Either `--suri` or `--secret-file` is required".into()),
+		};
+		let keystore = open_keystore(cli, &self.shared_params, &self.keystore_params)?;
+
+		let aura = insert::<sr25519::Pair>(&keystore, key_types::AURA, &suri)?;
+		let grandpa = insert::<ed25519::Pair>(&keystore, key_types::GRANDPA, &suri)?;
+		let ocw = insert::<sr25519::Pair>(&keystore, OCW_KEY_TYPE, &suri)?;
+
+		print_keys(aura, grandpa, ocw);
+		Ok(())
+	}
+}
+
+#[derive(Debug, StructOpt)]
+pub struct RotateCmd {
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub keystore_params: KeystoreParams,
+}
+
+impl RotateCmd {
+	/// Run the command.
+	pub fn run<C: SubstrateCli>(&self, cli: &C) -> Result<(), Error> {
+		let keystore = open_keystore(cli, &self.shared_params, &self.keystore_params)?;
+
+		let aura = SyncCryptoStore::sr25519_generate_new(&*keystore, key_types::AURA, None)
+			.map_err(|_| Error::KeyStoreOperation)?;
+		let grandpa = SyncCryptoStore::ed25519_generate_new(&*keystore, key_types::GRANDPA, None)
+			.map_err(|_| Error::KeyStoreOperation)?;
+		let ocw = SyncCryptoStore::sr25519_generate_new(&*keystore, OCW_KEY_TYPE, None)
+			.map_err(|_| Error::KeyStoreOperation)?;
+
+		print_keys(aura, grandpa, ocw);
+		Ok(())
+	}
+}
+
+/// Open the keystore the node would use for the chain selected in `shared_params`.
+fn open_keystore<C: SubstrateCli>(
+	cli: &C,
+	shared_params: &SharedParams,
+	keystore_params: &KeystoreParams,
+) -> Result<SyncCryptoStorePtr, Error> {
+	let base_path = shared_params.base_path()
+		.unwrap_or_else(|| BasePath::from_project("", "", &C::executable_name()));
+	let chain_id = shared_params.chain_id(shared_params.is_dev());
+	let chain_spec = cli.load_spec(&chain_id)?;
+	let config_dir = base_path.config_dir(chain_spec.id());
+
+	match keystore_params.keystore_config(&config_dir)? {
+		(_, KeystoreConfig::Path { path, password }) => {
+			let keystore = LocalKeystore::open(path, password)
+				.map_err(|e| format!("Failed to open keystore: {}", e))?;
+			Ok(Arc::new(keystore))
+		},
+		_ => unreachable!("keystore_config always returns path and password; qed"),
+	}
+}
+
+/// Derive a `P` key from `suri` and store it in `keystore` under `key_type`.
+fn insert<P: Pair>(
+	keystore: &SyncCryptoStorePtr,
+	key_type: KeyTypeId,
+	suri: &str,
+) -> Result<P::Public, Error> {
+	let pair = P::from_string(suri, None)
+		.map_err(|e| format!("Invalid secret URI: {:?}", e))?;
+	let public = pair.public();
+
+	SyncCryptoStore::insert_unknown(&**keystore, key_type, suri, public.as_slice())
+		.map_err(|_| Error::KeyStoreOperation)?;
+
+	Ok(public)
+}
+
+fn print_keys(aura: sr25519::Public, grandpa: ed25519::Public, ocw: sr25519::Public) {
+	let session_keys = SessionKeys {
+		aura: aura.into(),
+		grandpa: grandpa.into(),
+	};
+
+	println!("Aura (sr25519):            0x{}", HexDisplay::from(&aura.to_raw_vec()));
+	println!("GRANDPA (ed25519):         0x{}", HexDisplay::from(&grandpa.to_raw_vec()));
+	println!("Offchain worker (sr25519): 0x{}", HexDisplay::from(&ocw.to_raw_vec()));
+	// Same encoding `author_rotateKeys` returns.
+	println!("Session keys:              0x{}", HexDisplay::from(&session_keys.encode()));
+}
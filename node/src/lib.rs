@@ -1,3 +1,11 @@
+pub mod authority_keys;
+pub mod block_import;
 pub mod chain_spec;
+pub mod faucet_endpoint;
+pub mod health;
+pub mod index;
+pub mod metrics;
+pub mod ocw;
 pub mod rpc;
+pub mod scale;
 pub mod service;
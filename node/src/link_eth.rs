@@ -0,0 +1,234 @@
+//! `link-eth-sign`: build and sign the message `AccountLinkerModule::link_eth`
+//! expects, or check a signature against it.
+//!
+//! The message is `"Link Litentry: " ++ account ++ expiring block (u32 LE)`,
+//! signed as an Ethereum personal message (`"\x19Ethereum Signed Message:\n" ++ len`).
+
+use std::path::{Path, PathBuf};
+use codec::Encode;
+use structopt::StructOpt;
+use sc_cli::Error;
+use sp_core::{crypto::Ss58Codec, hashing::keccak_256, hexdisplay::HexDisplay, H160, H256};
+use litentry_runtime::{pallet_account_linker, AccountId, BlockNumber, Call, Runtime};
+
+/// Prefix of the message signed for `link_eth`.
+const MESSAGE_PREFIX: &[u8] = b"Link Litentry: ";
+
+#[derive(Debug, StructOpt)]
+pub struct LinkEthSignCmd {
+	/// Litentry account to link, as SS58 or hex.
+	#[structopt(long, parse(try_from_str = parse_account))]
+	pub account: AccountId,
+
+	/// Block number after which the signature expires.
+	#[structopt(long, value_name = "BLOCK")]
+	pub expiry: BlockNumber,
+
+	/// Slot of the link in the account's list of Ethereum addresses.
+	#[structopt(long, default_value = "0")]
+	pub index: u32,
+
+	/// File holding the hex-encoded Ethereum private key.
+	#[structopt(long, value_name = "PATH", parse(from_os_str), required_unless = "verify")]
+	pub key_file: Option<PathBuf>,
+
+	/// Check `--r`, `--s` and `--v` against `--address` instead of signing.
+	#[structopt(long, conflicts_with = "key-file", requires_all = &["address", "r", "s", "v"])]
+	pub verify: bool,
+
+	/// Ethereum address expected to have signed the message.
+	#[structopt(long)]
+	pub address: Option<H160>,
+
+	/// `r` of the signature.
+	#[structopt(long)]
+	pub r: Option<H256>,
+
+	/// `s` of the signature.
+	#[structopt(long)]
+	pub s: Option<H256>,
+
+	/// `v` of the signature, 27 or 28.
+	#[structopt(long)]
+	pub v: Option<u8>,
+}
+
+impl LinkEthSignCmd {
+	/// Run the command.
+	pub fn run(&self) -> Result<(), Error> {
+		let message = link_message(&self.account, self.expiry);
+		let hash = eth_message_hash(&message);
+
+		println!("Message:      0x{}", HexDisplay::from(&message));
+		println!("Message hash: 0x{}", HexDisplay::from(&hash));
+
+		match (&self.key_file, self.address, self.r, self.s, self.v) {
+			(Some(key_file), ..) => self.sign(key_file, &hash),
+			(None, Some(address), Some(r), Some(s), Some(v)) => verify(&hash, address, r, s, v),
+			_ => Err("Either `--key-file` or `--verify` with the signature is required".into()),
+		}
+	}
+
+	fn sign(&self, key_file: &Path, hash: &[u8; 32]) -> Result<(), Error> {
+		let key = std::fs::read_to_string(key_file)?;
+		let key = hex::decode(key.trim().trim_start_matches("0x"))
+			.map_err(|e| format!("Invalid private key in {}: {}", key_file.display(), e))?;
+		let mut secret = [0u8; 32];
+		if key.len() != secret.len() {
+			return Err(format!("Private key in {} is not 32 bytes", key_file.display()).into());
+		}
+		secret.copy_from_slice(&key);
+		let secret = secp256k1::SecretKey::parse(&secret)
+			.map_err(|e| format!("Invalid private key in {}: {:?}", key_file.display(), e))?;
+
+		let (address, r, s, v) = sign(&secret, hash);
+
+		println!("Address:      {:?}", address);
+		println!("r:            0x{}", HexDisplay::from(&r));
+		println!("s:            0x{}", HexDisplay::from(&s));
+		println!("v:            {}", v);
+
+		let call = Call::AccountLinkerModule(pallet_account_linker::Call::<Runtime>::link_eth(
+			self.account.clone(),
+			self.index,
+			address.0,
+			self.expiry,
+			r,
+			s,
+			v,
+		));
+		println!("Call:         0x{}", HexDisplay::from(&call.encode()));
+
+		Ok(())
+	}
+}
+
+/// The message signed to link an Ethereum address to `account` until `expiry`.
+pub fn link_message(account: &AccountId, expiry: BlockNumber) -> Vec<u8> {
+	let mut message = MESSAGE_PREFIX.to_vec();
+	message.extend_from_slice(account.as_ref());
+	message.extend_from_slice(&expiry.encode());
+	message
+}
+
+/// Hash of `message` signed as an Ethereum personal message.
+pub fn eth_message_hash(message: &[u8]) -> [u8; 32] {
+	let mut prefixed = format!("\x19Ethereum Signed Message:\n{}", message.len()).into_bytes();
+	prefixed.extend_from_slice(message);
+	keccak_256(&prefixed)
+}
+
+fn eth_address(public: &secp256k1::PublicKey) -> H160 {
+	H160::from_slice(&keccak_256(&public.serialize()[1..])[12..])
+}
+
+/// Sign `hash` with `secret`, returning the signer's address and `r`, `s` and `v`.
+fn sign(secret: &secp256k1::SecretKey, hash: &[u8; 32]) -> (H160, [u8; 32], [u8; 32], u8) {
+	let address = eth_address(&secp256k1::PublicKey::from_secret_key(secret));
+	let (signature, recovery_id) = secp256k1::sign(&secp256k1::Message::parse(hash), secret);
+	let signature = signature.serialize();
+	let mut r = [0u8; 32];
+	let mut s = [0u8; 32];
+	r.copy_from_slice(&signature[..32]);
+	s.copy_from_slice(&signature[32..]);
+
+	(address, r, s, recovery_id.serialize() + 27)
+}
+
+/// The address that signed `hash`. `v` must be 27 or 28, as wallets and `sign` produce it;
+/// a bare recovery id (0 or 1) is rejected rather than guessed at.
+fn recover(hash: &[u8; 32], r: H256, s: H256, v: u8) -> Result<H160, String> {
+	let recovery_id = match v {
+		27 | 28 => secp256k1::RecoveryId::parse(v - 27).expect("0 and 1 are valid recovery ids; qed"),
+		_ => return Err(format!("Invalid v {}, expected 27 or 28", v)),
+	};
+	let mut signature = [0u8; 64];
+	signature[..32].copy_from_slice(r.as_bytes());
+	signature[32..].copy_from_slice(s.as_bytes());
+	let signature = secp256k1::Signature::parse(&signature);
+
+	let public = secp256k1::recover(&secp256k1::Message::parse(hash), &signature, &recovery_id)
+		.map_err(|e| format!("Signature doesn't recover to any key: {:?}", e))?;
+	Ok(eth_address(&public))
+}
+
+fn verify(hash: &[u8; 32], expected: H160, r: H256, s: H256, v: u8) -> Result<(), Error> {
+	let recovered = recover(hash, r, s, v)?;
+
+	println!("Recovered:    {:?}", recovered);
+	if recovered != expected {
+		return Err(format!(
+			"Signature is from {:?}, not {:?}. Check the account, expiry and signing key.",
+			recovered, expected,
+		).into());
+	}
+
+	println!("Signature is valid for {:?}", expected);
+	Ok(())
+}
+
+fn parse_account(s: &str) -> Result<AccountId, String> {
+	if let Ok(account) = AccountId::from_ss58check(s) {
+		return Ok(account);
+	}
+
+	let bytes = hex::decode(s.trim_start_matches("0x"))
+		.map_err(|_| format!("`{}` is neither an SS58 address nor hex", s))?;
+	let mut raw = [0u8; 32];
+	if bytes.len() != raw.len() {
+		return Err(format!("`{}` is not a 32 byte account id", s));
+	}
+	raw.copy_from_slice(&bytes);
+
+	Ok(raw.into())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// The Ethereum key and address of `ts-tests/scripts/smoke-test.ts`.
+	const TS_TESTS_KEY: [u8; 32] = hex_literal::hex!("e82c0c4259710bb0d6cf9f9e8d0ad73419c1278a14d375e5ca691e7618103011");
+	const TS_TESTS_ADDRESS: [u8; 20] = hex_literal::hex!("4d88dc5d528a33e4b8be579e9476715f60060582");
+	/// `//Alice`, who links the address in `ts-tests`.
+	const ALICE: [u8; 32] = hex_literal::hex!("d43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d");
+
+	fn ts_tests_hash() -> [u8; 32] {
+		eth_message_hash(&link_message(&ALICE.into(), 10000))
+	}
+
+	#[test]
+	fn message_matches_ts_tests() {
+		let message = link_message(&ALICE.into(), 10000);
+
+		assert_eq!(message.len(), 51);
+		assert_eq!(
+			ts_tests_hash(),
+			hex_literal::hex!("e67c2b3a4a19666481510f5172835233aacf37a8e7378b8ffd2d43209411f4d0"),
+		);
+	}
+
+	#[test]
+	fn signature_recovers_signer() {
+		let secret = secp256k1::SecretKey::parse(&TS_TESTS_KEY).unwrap();
+		let hash = ts_tests_hash();
+		let (address, r, s, v) = sign(&secret, &hash);
+
+		assert_eq!(address, H160(TS_TESTS_ADDRESS));
+		assert!(v == 27 || v == 28);
+		assert_eq!(recover(&hash, r.into(), s.into(), v), Ok(address));
+		assert!(verify(&hash, address, r.into(), s.into(), v).is_ok());
+	}
+
+	#[test]
+	fn other_message_or_raw_recovery_id_fails() {
+		let secret = secp256k1::SecretKey::parse(&TS_TESTS_KEY).unwrap();
+		let hash = ts_tests_hash();
+		let (address, r, s, v) = sign(&secret, &hash);
+		let other = eth_message_hash(&link_message(&ALICE.into(), 10001));
+
+		assert_ne!(recover(&other, r.into(), s.into(), v), Ok(address));
+		assert!(verify(&other, address, r.into(), s.into(), v).is_err());
+		assert!(recover(&hash, r.into(), s.into(), v - 27).is_err());
+	}
+}
@@ -2,11 +2,31 @@
 #![warn(missing_docs)]
 
 mod chain_spec;
+mod block_import;
 #[macro_use]
 mod service;
+mod authority_keys;
+mod check_upgrade;
 mod cli;
 mod command;
+mod decode;
+mod export_types;
+mod faucet_endpoint;
+mod fork_off;
+mod health;
+mod index;
+mod inspect;
+mod key;
+mod link_eth;
+mod metrics;
+mod ocw;
+mod ocw_simulate;
 mod rpc;
+mod scale;
+mod token_server_mock;
+mod tx;
+mod verify_runtime;
+mod wasm_blob;
 
 fn main() -> sc_cli::Result<()> {
 	command::run()
@@ -0,0 +1,399 @@
+//! Litentry metrics on the node's Prometheus registry, derived from imported
+//! blocks and their events:
+//!
+//! - `litentry_finality_lag`: blocks between the best and the finalized block.
+//! - `litentry_link_eth_extrinsics_per_block`: `link_eth` calls in the last best block.
+//! - `litentry_link_eth_extrinsics_total{result}`: `link_eth` calls by outcome,
+//!   `unknown` if the block's events don't decode that far.
+//! - `litentry_ocw_submissions_total{call}`: `OffchainWorkerModule` calls other
+//!   than `asset_claim`, i.e. what the offchain workers submit.
+//! - `litentry_ocw_submissions_last_session`: those submissions in the last
+//!   complete query session of `QuerySessionLength` blocks.
+//! - `litentry_asset_claims_pending`: entries of the offchain worker pallet's
+//!   `ClaimAccountSet`, the claims waiting for a query session.
+//! - `litentry_token_server_probe_request_duration_seconds` and
+//!   `litentry_token_server_probe_errors_total`: the offchain workers' HTTP
+//!   requests run in the runtime where the node can't time them, so the node
+//!   probes the token server given in `--token-server-probe` itself once per query
+//!   session. These measure the probes, not the offchain workers' requests.
+//!
+//! The gauges follow the best block. The totals and the submissions per session
+//! count finalized blocks, each once, so reorgs don't count a fork twice.
+
+use std::{sync::Arc, time::{Duration, Instant}};
+use codec::Encode;
+use futures::{future, StreamExt};
+use prometheus_endpoint::{
+	register, Counter, CounterVec, Gauge, Histogram, HistogramOpts, Opts, PrometheusError, Registry, U64,
+};
+use serde_json::Value;
+use sc_client_api::{Backend, BlockBackend, BlockchainEvents, StorageProvider};
+use sp_api::{Metadata as MetadataApi, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_core::{hashing::twox_128, storage::{well_known_keys, StorageKey}};
+use sp_runtime::{generic::BlockId, traits::{Block as BlockT, Header as HeaderT}};
+use litentry_runtime::{opaque::Block, Hash, QuerySessionLength};
+
+use crate::scale::{Events, Metadata, StorageEntryType};
+
+/// Longest a token server probe may take before it counts as an error.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Storage item of the offchain worker pallet with the accounts whose claims
+/// wait for the next query session.
+const PENDING_CLAIMS: &str = "ClaimAccountSet";
+
+/// The Litentry metrics.
+#[derive(Clone)]
+pub struct Metrics {
+	finality_lag: Gauge<U64>,
+	link_eth_per_block: Gauge<U64>,
+	link_eth: CounterVec<U64>,
+	ocw_submissions: CounterVec<U64>,
+	ocw_submissions_last_session: Gauge<U64>,
+	asset_claims_pending: Gauge<U64>,
+	token_server_duration: Histogram,
+	token_server_errors: Counter<U64>,
+}
+
+impl Metrics {
+	/// Create the metrics and register them on `registry`.
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Metrics {
+			finality_lag: register(Gauge::new(
+				"litentry_finality_lag",
+				"Number of blocks between the best and the finalized block",
+			)?, registry)?,
+			link_eth_per_block: register(Gauge::new(
+				"litentry_link_eth_extrinsics_per_block",
+				"Number of link_eth extrinsics in the last best block",
+			)?, registry)?,
+			link_eth: register(CounterVec::new(
+				Opts::new("litentry_link_eth_extrinsics_total", "Number of finalized link_eth extrinsics by result"),
+				&["result"],
+			)?, registry)?,
+			ocw_submissions: register(CounterVec::new(
+				Opts::new(
+					"litentry_ocw_submissions_total",
+					"Number of finalized extrinsics submitted by offchain workers by call",
+				),
+				&["call"],
+			)?, registry)?,
+			ocw_submissions_last_session: register(Gauge::new(
+				"litentry_ocw_submissions_last_session",
+				"Number of extrinsics submitted by offchain workers in the last complete query session",
+			)?, registry)?,
+			asset_claims_pending: register(Gauge::new(
+				"litentry_asset_claims_pending",
+				"Number of asset claims waiting for the offchain workers",
+			)?, registry)?,
+			token_server_duration: register(Histogram::with_opts(
+				HistogramOpts::new(
+					"litentry_token_server_probe_request_duration_seconds",
+					"Duration of successful token server probes by the node",
+				).buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]),
+			)?, registry)?,
+			token_server_errors: register(Counter::new(
+				"litentry_token_server_probe_errors_total",
+				"Number of failed token server probes by the node",
+			)?, registry)?,
+		})
+	}
+}
+
+/// Query session of the offchain worker pallet that block `number` belongs to.
+fn session_of(number: u32) -> u32 {
+	number / QuerySessionLength::get()
+}
+
+struct BlockMetrics<C, B> {
+	client: Arc<C>,
+	metrics: Metrics,
+	/// The metadata of the runtime the last block was executed with, by code hash.
+	metadata: Option<(Option<Hash>, Metadata)>,
+	/// The current query session and the finalized submissions in it so far.
+	session: Option<(u32, u64)>,
+	/// The last finalized block counted.
+	last_finalized: Option<u32>,
+	/// Whether the missing pending claims storage item was reported.
+	claims_missing_reported: bool,
+	_backend: std::marker::PhantomData<B>,
+}
+
+impl<C, B> BlockMetrics<C, B> where
+	C: HeaderBackend<Block> + BlockBackend<Block> + StorageProvider<Block, B> + ProvideRuntimeApi<Block>,
+	C::Api: MetadataApi<Block>,
+	B: Backend<Block>,
+{
+	/// Fetch the metadata at `at` if the runtime code changed since the last block.
+	fn update_metadata(&mut self, at: &BlockId<Block>) -> Result<(), String> {
+		let code_hash = self.client.storage_hash(at, &StorageKey(well_known_keys::CODE.to_vec()))
+			.map_err(|e| format!("Failed to read the code hash at {:?}: {:?}", at, e))?;
+
+		if self.metadata.as_ref().map(|(hash, _)| *hash) != Some(code_hash) {
+			let metadata = self.client.runtime_api().metadata(at)
+				.map_err(|e| format!("Failed to get the metadata at {:?}: {:?}", at, e))?;
+			self.metadata = Some((code_hash, Metadata::from_encoded(&metadata)?));
+		}
+		Ok(())
+	}
+
+	fn storage(&self, at: &BlockId<Block>, key: Vec<u8>) -> Result<Option<Vec<u8>>, String> {
+		self.client.storage(at, &StorageKey(key))
+			.map(|value| value.map(|value| value.0))
+			.map_err(|e| format!("Failed to read storage at {:?}: {:?}", at, e))
+	}
+
+	/// Pallet and name of each decodable call in the block `at`, and whether it
+	/// succeeded, if the events of the block decode that far.
+	fn calls(&mut self, at: &BlockId<Block>) -> Result<Vec<(String, String, Option<bool>)>, String> {
+		self.update_metadata(at)?;
+		let metadata = &self.metadata.as_ref().expect("updated above; qed").1;
+
+		let extrinsics = self.client.block_body(at)
+			.map_err(|e| format!("Failed to read the body of {:?}: {:?}", at, e))?
+			.ok_or_else(|| format!("Body of block {:?} not found", at))?;
+		let events = self.storage(at, [twox_128(b"System"), twox_128(b"Events")].concat())?
+			.unwrap_or_else(|| vec![0]);
+		let events = metadata.decode_events_lossy(&events)?;
+		if let Some(undecoded) = &events.undecoded {
+			log::debug!(
+				"Metrics: events {} to {} of {:?} not decoded: {}",
+				undecoded.index, undecoded.index + undecoded.count - 1, at, undecoded.error,
+			);
+		}
+
+		let mut calls = Vec::new();
+		for (index, extrinsic) in extrinsics.iter().enumerate() {
+			let decoded = match metadata.decode_extrinsic(&extrinsic.encode()) {
+				Ok(decoded) => decoded,
+				Err(e) => {
+					log::debug!("Metrics: extrinsic {} of {:?} not decoded: {}", index, at, e);
+					continue;
+				},
+			};
+			if let (Some(pallet), Some(call)) = (decoded["call"]["pallet"].as_str(), decoded["call"]["call"].as_str()) {
+				calls.push((pallet.to_string(), call.to_string(), succeeded(&events, index)));
+			}
+		}
+		Ok(calls)
+	}
+
+	fn new_best(&mut self, header: &<Block as BlockT>::Header) -> Result<(), String> {
+		let info = self.client.info();
+		self.metrics.finality_lag.set(info.best_number.saturating_sub(info.finalized_number).into());
+
+		let at = BlockId::Hash(header.hash());
+		let link_eth = self.calls(&at)?.iter()
+			.filter(|(pallet, call, _)| pallet == "AccountLinkerModule" && call == "link_eth")
+			.count();
+		self.metrics.link_eth_per_block.set(link_eth as u64);
+
+		if let Some(pending) = self.pending_claims(&at)? {
+			self.metrics.asset_claims_pending.set(pending);
+		}
+
+		Ok(())
+	}
+
+	/// Count the blocks up to `header`, which was just finalized, that weren't yet.
+	fn finalized(&mut self, header: &<Block as BlockT>::Header) -> Result<(), String> {
+		let number = *header.number();
+		let first = self.last_finalized.map_or(number, |last| last + 1);
+		for number in first..=number {
+			let hash = self.client.hash(number)
+				.map_err(|e| format!("Failed to get the hash of #{}: {:?}", number, e))?
+				.ok_or_else(|| format!("Finalized block #{} not found", number))?;
+			self.count_finalized(number, &BlockId::Hash(hash))?;
+			self.last_finalized = Some(number);
+		}
+		Ok(())
+	}
+
+	fn count_finalized(&mut self, number: u32, at: &BlockId<Block>) -> Result<(), String> {
+		let mut submissions = 0;
+		for (pallet, call, success) in self.calls(at)? {
+			match (pallet.as_str(), call.as_str()) {
+				("AccountLinkerModule", "link_eth") => {
+					let result = match success {
+						Some(true) => "success",
+						Some(false) => "failed",
+						None => "unknown",
+					};
+					self.metrics.link_eth.with_label_values(&[result]).inc();
+				},
+				("OffchainWorkerModule", call) if call != "asset_claim" => {
+					submissions += 1;
+					self.metrics.ocw_submissions.with_label_values(&[call]).inc();
+				},
+				_ => (),
+			}
+		}
+
+		let session = session_of(number);
+		self.session = match self.session {
+			Some((current, count)) if current == session => Some((session, count + submissions)),
+			Some((current, count)) => {
+				if session == current + 1 {
+					self.metrics.ocw_submissions_last_session.set(count);
+				}
+				Some((session, submissions))
+			},
+			None => Some((session, submissions)),
+		};
+
+		Ok(())
+	}
+
+	/// Entries of the offchain worker pallet's [`PENDING_CLAIMS`] at `at`, or
+	/// `None` if the runtime has no such item.
+	fn pending_claims(&mut self, at: &BlockId<Block>) -> Result<Option<u64>, String> {
+		self.update_metadata(at)?;
+		let metadata = &self.metadata.as_ref().expect("updated above; qed").1;
+		let entry = metadata.module("OffchainWorkerModule")?.storage.as_ref().and_then(|storage| {
+			storage.entries.iter().find(|entry| entry.name == PENDING_CLAIMS).map(|entry| (storage, entry))
+		});
+		let (storage, entry) = match entry {
+			Some(entry) => entry,
+			None => {
+				if !self.claims_missing_reported {
+					log::warn!(
+						"Metrics: OffchainWorkerModule has no `{}` storage, litentry_asset_claims_pending isn't updated",
+						PENDING_CLAIMS,
+					);
+				}
+				self.claims_missing_reported = true;
+				return Ok(None);
+			},
+		};
+		let key = [twox_128(storage.prefix.as_bytes()), twox_128(entry.name.as_bytes())].concat();
+
+		match &entry.ty {
+			StorageEntryType::Plain(ty) => match self.storage(at, key)? {
+				Some(value) => match metadata.decode(ty, &value)? {
+					Value::Array(claims) => Ok(Some(claims.len() as u64)),
+					_ => Ok(None),
+				},
+				None => Ok(Some(0)),
+			},
+			_ => self.client.storage_keys(at, &StorageKey(key))
+				.map(|keys| Some(keys.len() as u64))
+				.map_err(|e| format!("Failed to list the claims at {:?}: {:?}", at, e)),
+		}
+	}
+}
+
+/// Whether the extrinsic at `index` emitted `System::ExtrinsicSuccess`, `None`
+/// if that may be among the events that didn't decode.
+fn succeeded(events: &Events, index: usize) -> Option<bool> {
+	let succeeded = events.decoded.iter().any(|event| {
+		event["phase"]["ApplyExtrinsic"] == Value::from(index)
+			&& event["pallet"] == "System"
+			&& event["event"] == "ExtrinsicSuccess"
+	});
+	match events.undecoded {
+		Some(_) if !succeeded => None,
+		_ => Some(succeeded),
+	}
+}
+
+/// `GET` the token server the offchain workers use.
+async fn probe_token_server(url: &str, metrics: &Metrics) {
+	let uri = match url.parse::<hyper::Uri>() {
+		Ok(uri) if uri.scheme_str() == Some("http") => uri,
+		_ => {
+			log::debug!("Metrics: can only probe http:// token servers, not {}", url);
+			return;
+		},
+	};
+
+	let started = Instant::now();
+	let request = async {
+		let response = hyper::Client::new().get(uri).await.map_err(|e| e.to_string())?;
+		if !response.status().is_success() {
+			return Err(format!("status {}", response.status()));
+		}
+		hyper::body::to_bytes(response.into_body()).await.map_err(|e| e.to_string())
+	};
+
+	match tokio::time::timeout(PROBE_TIMEOUT, request).await {
+		Ok(Ok(_)) => metrics.token_server_duration.observe(started.elapsed().as_secs_f64()),
+		Ok(Err(e)) => {
+			log::debug!("Metrics: token server {} failed: {}", url, e);
+			metrics.token_server_errors.inc();
+		},
+		Err(_) => {
+			log::debug!("Metrics: token server {} timed out", url);
+			metrics.token_server_errors.inc();
+		},
+	}
+}
+
+/// Update `metrics` from every new best and finalized block until the node
+/// stops, probing `token_server` if given.
+pub async fn run<C, B>(client: Arc<C>, metrics: Metrics, token_server: Option<String>) where
+	C: HeaderBackend<Block> + BlockBackend<Block> + StorageProvider<Block, B> + ProvideRuntimeApi<Block>
+		+ BlockchainEvents<Block> + Send + Sync + 'static,
+	C::Api: MetadataApi<Block>,
+	B: Backend<Block> + 'static,
+{
+	let blocks = {
+		let mut imported = client.import_notification_stream().fuse();
+		let mut finalized = client.finality_notification_stream().fuse();
+		let mut blocks = BlockMetrics {
+			client: client.clone(),
+			metrics: metrics.clone(),
+			metadata: None,
+			session: None,
+			last_finalized: None,
+			claims_missing_reported: false,
+			_backend: Default::default(),
+		};
+		async move {
+			loop {
+				futures::select! {
+					notification = imported.next() => match notification {
+						Some(notification) if notification.is_new_best => {
+							if let Err(e) = blocks.new_best(&notification.header) {
+								log::warn!("Metrics: failed to process block #{}: {}", notification.header.number(), e);
+							}
+						},
+						Some(_) => (),
+						None => break,
+					},
+					notification = finalized.next() => match notification {
+						Some(notification) => {
+							if let Err(e) = blocks.finalized(&notification.header) {
+								log::warn!(
+									"Metrics: failed to count finalized block #{}: {}",
+									notification.header.number(), e,
+								);
+							}
+						},
+						None => break,
+					},
+				}
+			}
+		}
+	};
+
+	let token_server = {
+		let mut notifications = client.import_notification_stream();
+		async move {
+			let url = match token_server {
+				Some(url) => url,
+				None => return,
+			};
+			let mut probed = None;
+			while let Some(notification) = notifications.next().await {
+				let session = session_of(*notification.header.number());
+				if notification.is_new_best && probed != Some(session) {
+					probed = Some(session);
+					probe_token_server(&url, &metrics).await;
+				}
+			}
+		}
+	};
+
+	future::join(blocks, token_server).await;
+}
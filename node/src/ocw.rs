@@ -0,0 +1,344 @@
+//! Offchain worker configuration.
+//!
+//! The offchain worker reads its settings, such as the API keys of the services
+//! it queries, from offchain persistent storage, under keys the pallet defines.
+//! The node writes the values given in `--ocw-config` there at startup, so they
+//! don't have to be pushed over unsafe RPC after boot.
+//!
+//! The workers run through `sc_offchain::notification_future`, as in
+//! `sc_service::build_offchain_workers`, with a spawner that records how long
+//! each run takes, see [`ActivitySpawner`]. For local runs HTTP can also be
+//! answered from fixture files, see [`run_with_http_fixtures`].
+
+use std::{
+	collections::BTreeMap,
+	fmt::Debug,
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+use codec::{Decode, Encode};
+use futures::{future::BoxFuture, Future, FutureExt, StreamExt};
+use serde::Deserialize;
+use litentry_runtime::opaque::{Block, Header};
+use sc_client_api::{Backend, BlockchainEvents, CallExecutor, ExecutorProvider, StateBackend};
+use sc_service::SpawnTaskHandle;
+use sp_blockchain::HeaderBackend;
+use sp_core::{
+	hashing::twox_128,
+	offchain::{self, OffchainExt, OffchainStorage, Timestamp, TransactionPoolExt},
+	traits::SpawnNamed,
+};
+use sp_externalities::Extensions;
+use sp_keystore::{KeystoreExt, SyncCryptoStorePtr};
+use sp_runtime::{generic::BlockId, traits::{Block as BlockT, Header as HeaderT}};
+use sp_state_machine::ExecutionStrategy;
+use sp_transaction_pool::LocalTransactionPool;
+
+use self::mock::{HttpFixtures, MockExternalities};
+
+pub mod mock;
+
+/// Contents of the `--ocw-config` file.
+///
+/// The offchain worker pallet defines the keys it reads, so they are named here
+/// as they are, without a prefix added by the node. Keys starting with `0x` are
+/// hex.
+///
+/// ```toml
+/// [storage]
+/// "<key>" = "<value>"
+///
+/// [storage-files]
+/// "<key>" = "/run/secrets/<secret>"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct OcwConfigFile {
+	/// Values written to offchain storage as they are.
+	#[serde(default)]
+	pub storage: BTreeMap<String, String>,
+	/// Files whose trimmed content is written to offchain storage, for secrets.
+	#[serde(default)]
+	pub storage_files: BTreeMap<String, PathBuf>,
+}
+
+impl OcwConfigFile {
+	/// Parse the config file at `path`.
+	pub fn load(path: &Path) -> Result<Self, String> {
+		let content = std::fs::read_to_string(path)
+			.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+		toml::from_str(&content)
+			.map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+	}
+}
+
+/// Offchain worker settings with all secrets loaded.
+#[derive(Default)]
+pub struct OcwConfig {
+	/// Values to write to offchain persistent storage, by key.
+	pub storage: BTreeMap<Vec<u8>, String>,
+}
+
+/// An offchain storage key as given in the config: hex with `0x`, otherwise its bytes.
+fn storage_key(key: &str) -> Result<Vec<u8>, String> {
+	match key.strip_prefix("0x") {
+		Some(hex) => hex::decode(hex).map_err(|e| format!("Invalid hex storage key `{}`: {}", key, e)),
+		None => Ok(key.as_bytes().to_vec()),
+	}
+}
+
+impl OcwConfig {
+	/// Load the config, reading every file referenced by `storage_files`.
+	pub fn from_file(file: OcwConfigFile) -> Result<Self, String> {
+		let mut storage = file.storage.iter()
+			.map(|(key, value)| Ok((storage_key(key)?, value.clone())))
+			.collect::<Result<BTreeMap<_, _>, String>>()?;
+		for (key, path) in &file.storage_files {
+			let value = std::fs::read_to_string(path)
+				.map_err(|e| format!("Failed to read the value of `{}` from {}: {}", key, path.display(), e))?;
+			storage.insert(storage_key(key)?, value.trim().to_string());
+		}
+
+		Ok(OcwConfig { storage })
+	}
+
+	/// Write the settings into offchain persistent storage, SCALE-encoded as `Vec<u8>`.
+	pub fn write_to<S: OffchainStorage>(&self, storage: &mut S) {
+		for (key, value) in &self.storage {
+			storage.set(sp_offchain::STORAGE_PREFIX, key, &value.as_bytes().encode());
+		}
+
+		log::info!(
+			"Offchain worker configured: {} offchain storage values written",
+			self.storage.len(),
+		);
+	}
+}
+
+/// Runtime API method running the offchain worker.
+const OFFCHAIN_WORKER_METHOD: &str = "OffchainWorkerApi_offchain_worker";
+
+/// Run the runtime's offchain worker for `header` on top of the given externalities.
+pub fn execute<C, O, P>(
+	client: &C,
+	header: &Header,
+	offchain: O,
+	pool: P,
+	keystore: SyncCryptoStorePtr,
+) -> Result<(), sp_blockchain::Error> where
+	C: ExecutorProvider<Block>,
+	O: offchain::Externalities + 'static,
+	P: offchain::TransactionPool + Send + 'static,
+{
+	let mut extensions = Extensions::new();
+	extensions.register(OffchainExt::new(offchain));
+	extensions.register(TransactionPoolExt::new(pool));
+	extensions.register(KeystoreExt(keystore));
+
+	client.executor().call(
+		&BlockId::Hash(header.hash()),
+		OFFCHAIN_WORKER_METHOD,
+		&header.encode(),
+		ExecutionStrategy::NativeElseWasm,
+		Some(extensions),
+	).map(drop)
+}
+
+/// Timestamp of the block `hash`, read from `Timestamp::Now`.
+pub fn block_timestamp<B: Backend<Block>>(
+	backend: &B,
+	hash: <Block as BlockT>::Hash,
+) -> Result<Timestamp, String> {
+	let key = [twox_128(b"Timestamp"), twox_128(b"Now")].concat();
+	let value = backend.state_at(BlockId::Hash(hash))
+		.map_err(|e| format!("State of {} unavailable: {:?}", hash, e))?
+		.storage(&key)
+		.map_err(|e| format!("Failed to read the timestamp of {}: {:?}", hash, e))?
+		.unwrap_or_default();
+	let millis = u64::decode(&mut &value[..])
+		.map_err(|e| format!("Invalid timestamp at {}: {:?}", hash, e))?;
+
+	Ok(Timestamp::from_unix_millis(millis))
+}
+
+/// Submits the offchain worker's transactions to the local transaction pool.
+pub struct PoolSubmitter<P> {
+	pool: Arc<P>,
+	at: BlockId<Block>,
+}
+
+impl<P> PoolSubmitter<P> {
+	/// Submit to `pool`, validating against the block `at`.
+	pub fn new(pool: Arc<P>, at: BlockId<Block>) -> Self {
+		PoolSubmitter { pool, at }
+	}
+}
+
+impl<P> offchain::TransactionPool for PoolSubmitter<P> where
+	P: LocalTransactionPool<Block = Block>,
+	P::Error: Debug,
+{
+	fn submit_transaction(&mut self, extrinsic: Vec<u8>) -> Result<(), ()> {
+		let extrinsic = <Block as BlockT>::Extrinsic::decode(&mut &extrinsic[..])
+			.map_err(|e| log::warn!("Offchain worker submitted an undecodable transaction: {:?}", e))?;
+
+		self.pool.submit_local(&self.at, extrinsic)
+			.map(drop)
+			.map_err(|e| log::warn!("Failed to submit offchain worker transaction: {:?}", e))
+	}
+}
+
+/// Offchain worker runs in progress and the last finished one.
+#[derive(Debug, Default)]
+pub struct OcwActivity {
+	state: Mutex<ActivityState>,
+}
+
+#[derive(Debug, Default)]
+struct ActivityState {
+	next_run: u64,
+	running: BTreeMap<u64, (u32, Instant)>,
+	last_finished: Option<(u32, Instant)>,
+}
+
+impl OcwActivity {
+	/// Record `run`, the offchain worker of block `number`, while it executes.
+	pub fn track<F: Future<Output = ()>>(self: &Arc<Self>, number: u32, run: F) -> impl Future<Output = ()> {
+		let id = {
+			let mut state = self.state.lock().expect("OCW activity lock poisoned");
+			let id = state.next_run;
+			state.next_run += 1;
+			state.running.insert(id, (number, Instant::now()));
+			id
+		};
+		let activity = self.clone();
+
+		run.map(move |()| {
+			let mut state = activity.state.lock().expect("OCW activity lock poisoned");
+			state.running.remove(&id);
+			state.last_finished = Some((number, Instant::now()));
+		})
+	}
+
+	/// Block number and running time of the longest running offchain worker.
+	pub fn longest_running(&self) -> Option<(u32, Duration)> {
+		let state = self.state.lock().expect("OCW activity lock poisoned");
+		state.running.values()
+			.map(|(number, started)| (*number, started.elapsed()))
+			.max_by_key(|(_, running)| *running)
+	}
+
+	/// Block number of the last finished offchain worker and the time since.
+	pub fn last_finished(&self) -> Option<(u32, Duration)> {
+		let state = self.state.lock().expect("OCW activity lock poisoned");
+		state.last_finished.map(|(number, finished)| (number, finished.elapsed()))
+	}
+}
+
+/// Task name of an offchain worker run, as spawned by `sc_offchain`.
+const OFFCHAIN_ON_BLOCK: &str = "offchain-on-block";
+
+/// Spawner for `sc_offchain::notification_future` that records each offchain
+/// worker run in an [`OcwActivity`].
+///
+/// `notification_future` spawns a run when a block becomes the new best block,
+/// so the run is attributed to the best block at the time it is spawned.
+#[derive(Clone)]
+pub struct ActivitySpawner<C> {
+	client: Arc<C>,
+	spawn_handle: SpawnTaskHandle,
+	activity: Arc<OcwActivity>,
+}
+
+impl<C> ActivitySpawner<C> {
+	/// Spawn with `spawn_handle`, recording runs in `activity`.
+	pub fn new(client: Arc<C>, spawn_handle: SpawnTaskHandle, activity: Arc<OcwActivity>) -> Self {
+		ActivitySpawner { client, spawn_handle, activity }
+	}
+}
+
+impl<C: HeaderBackend<Block>> ActivitySpawner<C> {
+	fn track(&self, run: BoxFuture<'static, ()>) -> impl Future<Output = ()> {
+		self.activity.track(self.client.info().best_number, run)
+	}
+}
+
+impl<C: HeaderBackend<Block> + Send + Sync + 'static> SpawnNamed for ActivitySpawner<C> {
+	fn spawn_blocking(&self, name: &'static str, future: BoxFuture<'static, ()>) {
+		match name {
+			OFFCHAIN_ON_BLOCK => self.spawn_handle.spawn_blocking(name, self.track(future)),
+			_ => self.spawn_handle.spawn_blocking(name, future),
+		}
+	}
+
+	fn spawn(&self, name: &'static str, future: BoxFuture<'static, ()>) {
+		match name {
+			OFFCHAIN_ON_BLOCK => self.spawn_handle.spawn(name, self.track(future)),
+			_ => self.spawn_handle.spawn(name, future),
+		}
+	}
+}
+
+/// Run the offchain worker on every new best block, like
+/// `sc_offchain::notification_future`, but with HTTP requests answered from
+/// `fixtures` and a deterministic clock and seed. Runs are spawned with
+/// `spawner`, under the same name as those of `notification_future`.
+pub async fn run_with_http_fixtures<C, B, P, S>(
+	client: Arc<C>,
+	backend: Arc<B>,
+	fixtures: Arc<HttpFixtures>,
+	pool: Arc<P>,
+	keystore: SyncCryptoStorePtr,
+	is_validator: bool,
+	spawner: S,
+) where
+	C: BlockchainEvents<Block> + ExecutorProvider<Block> + Send + Sync + 'static,
+	S: SpawnNamed,
+	B: Backend<Block> + 'static,
+	P: LocalTransactionPool<Block = Block> + 'static,
+	P::Error: Debug,
+{
+	let storage = match backend.offchain_storage() {
+		Some(storage) => storage,
+		None => {
+			log::error!("Offchain workers disabled: the backend has no offchain storage");
+			return;
+		},
+	};
+	let mut notifications = client.import_notification_stream();
+
+	while let Some(notification) = notifications.next().await {
+		if !notification.is_new_best {
+			continue;
+		}
+
+		let header = notification.header;
+		let hash = header.hash();
+		let timestamp = match block_timestamp(&*backend, hash) {
+			Ok(timestamp) => timestamp,
+			Err(e) => {
+				log::error!("Skipping offchain worker at #{}: {}", header.number(), e);
+				continue;
+			},
+		};
+
+		let offchain = MockExternalities::new(
+			fixtures.clone(),
+			storage.clone(),
+			is_validator,
+			timestamp,
+			hash.as_ref(),
+		);
+		let submitter = PoolSubmitter::new(pool.clone(), BlockId::Hash(hash));
+		let client = client.clone();
+		let keystore = keystore.clone();
+
+		spawner.spawn_blocking(OFFCHAIN_ON_BLOCK, Box::pin(async move {
+			if let Err(e) = execute(&*client, &header, offchain, submitter, keystore) {
+				log::error!("Offchain worker failed at #{}: {:?}", header.number(), e);
+			}
+		}));
+	}
+}
@@ -0,0 +1,523 @@
+//! Offchain externalities answering HTTP requests from fixture files.
+//!
+//! Everything the runtime can observe is deterministic: the clock is the block
+//! timestamp, the random seed is derived from the block hash and HTTP responses
+//! come from the fixtures, so repeated runs over the same chain behave the same.
+
+use std::{
+	collections::{BTreeMap, HashMap},
+	path::Path,
+	sync::{Arc, Mutex},
+};
+use serde::Deserialize;
+use sp_core::{
+	hashing::blake2_256,
+	offchain::{
+		self, Externalities, HttpError, HttpRequestId, HttpRequestStatus, OffchainStorage,
+		OpaqueNetworkState, OpaquePeerId, StorageKind, Timestamp,
+	},
+};
+
+/// Status returned for requests without a matching fixture.
+const NOT_FOUND: u16 = 404;
+
+/// One canned HTTP response.
+///
+/// ```json
+/// {
+///   "url-prefix": "https://api.etherscan.io/api?module=account&action=balancemulti",
+///   "status": 200,
+///   "body": { "status": "1", "message": "OK", "result": [] }
+/// }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Fixture {
+	/// HTTP method the fixture answers.
+	#[serde(default = "default_method")]
+	pub method: String,
+	/// URL the fixture answers, matched exactly.
+	#[serde(default)]
+	pub url: Option<String>,
+	/// URL prefix the fixture answers. Used when no fixture matches the URL exactly;
+	/// the longest prefix wins. Handy for URLs carrying API keys.
+	#[serde(default)]
+	pub url_prefix: Option<String>,
+	/// Response status.
+	#[serde(default = "default_status")]
+	pub status: u16,
+	/// Response headers.
+	#[serde(default)]
+	pub headers: BTreeMap<String, String>,
+	/// Response body. Strings are served verbatim, anything else as JSON.
+	#[serde(default)]
+	pub body: serde_json::Value,
+}
+
+fn default_method() -> String {
+	"GET".into()
+}
+
+fn default_status() -> u16 {
+	200
+}
+
+impl Fixture {
+	fn body(&self) -> Vec<u8> {
+		match &self.body {
+			serde_json::Value::String(body) => body.as_bytes().to_vec(),
+			serde_json::Value::Null => Vec::new(),
+			body => serde_json::to_vec(body).expect("JSON values always serialize; qed"),
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FixtureFile {
+	One(Fixture),
+	Many(Vec<Fixture>),
+}
+
+/// The fixtures of a `--offchain-http-mock` directory.
+#[derive(Debug, Default)]
+pub struct HttpFixtures {
+	fixtures: Vec<Fixture>,
+}
+
+impl HttpFixtures {
+	/// Load every `*.json` file in `dir`. A file holds one fixture or a list of them.
+	pub fn load(dir: &Path) -> Result<Self, String> {
+		let mut paths = std::fs::read_dir(dir)
+			.map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?
+			.map(|entry| entry.map(|entry| entry.path()))
+			.collect::<Result<Vec<_>, _>>()
+			.map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+		paths.retain(|path| path.extension().map_or(false, |ext| ext == "json"));
+		// Directory order is platform dependent, fixture precedence must not be.
+		paths.sort();
+
+		let mut fixtures = Vec::new();
+		for path in paths {
+			let content = std::fs::read(&path)
+				.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+			let file = serde_json::from_slice(&content)
+				.map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+
+			match file {
+				FixtureFile::One(fixture) => fixtures.push(fixture),
+				FixtureFile::Many(many) => fixtures.extend(many),
+			}
+		}
+
+		if let Some(fixture) = fixtures.iter().find(|f| f.url.is_none() && f.url_prefix.is_none()) {
+			return Err(format!("{} fixture without `url` or `url-prefix`", fixture.method));
+		}
+
+		log::info!("Serving offchain worker HTTP requests from {} fixtures in {}", fixtures.len(), dir.display());
+		Ok(HttpFixtures { fixtures })
+	}
+
+	/// The fixture answering `method` `url`, if any.
+	pub fn find(&self, method: &str, url: &str) -> Option<&Fixture> {
+		let candidates = || self.fixtures.iter()
+			.filter(move |fixture| fixture.method.eq_ignore_ascii_case(method));
+
+		candidates()
+			.find(|fixture| fixture.url.as_deref() == Some(url))
+			.or_else(|| {
+				candidates()
+					.filter(|fixture| fixture.url_prefix.as_deref().map_or(false, |prefix| url.starts_with(prefix)))
+					.max_by_key(|fixture| fixture.url_prefix.as_ref().map(String::len))
+			})
+	}
+}
+
+/// An HTTP request made by the offchain worker and the status it was answered with.
+#[derive(Debug, Clone)]
+pub struct HttpExchange {
+	/// Request method.
+	pub method: String,
+	/// Request URL.
+	pub url: String,
+	/// Request headers.
+	pub headers: Vec<(String, String)>,
+	/// Request body.
+	pub body: Vec<u8>,
+	/// Status of the served response.
+	pub status: u16,
+}
+
+/// Requests answered so far, shared with whoever wants to inspect them after the run.
+pub type HttpLog = Arc<Mutex<Vec<HttpExchange>>>;
+
+struct Response {
+	status: u16,
+	headers: Vec<(Vec<u8>, Vec<u8>)>,
+	body: Vec<u8>,
+	read: usize,
+}
+
+struct Request {
+	method: String,
+	url: String,
+	headers: Vec<(String, String)>,
+	body: Vec<u8>,
+	response: Option<Response>,
+}
+
+/// Offchain externalities with HTTP served from [`HttpFixtures`].
+pub struct MockExternalities<S> {
+	fixtures: Arc<HttpFixtures>,
+	storage: S,
+	is_validator: bool,
+	timestamp: Timestamp,
+	seed: [u8; 32],
+	requests: HashMap<u16, Request>,
+	next_request_id: u16,
+	log: HttpLog,
+}
+
+impl<S> MockExternalities<S> {
+	/// Create externalities for a worker run at a block with the given `timestamp`,
+	/// with the random seed derived from `seed`.
+	pub fn new(
+		fixtures: Arc<HttpFixtures>,
+		storage: S,
+		is_validator: bool,
+		timestamp: Timestamp,
+		seed: &[u8],
+	) -> Self {
+		MockExternalities {
+			fixtures,
+			storage,
+			is_validator,
+			timestamp,
+			seed: blake2_256(seed),
+			requests: Default::default(),
+			next_request_id: 0,
+			log: Default::default(),
+		}
+	}
+
+	/// Requests answered by these externalities.
+	pub fn log(&self) -> HttpLog {
+		self.log.clone()
+	}
+
+	fn respond(&mut self, id: u16) -> Option<&mut Response> {
+		let fixtures = &self.fixtures;
+		let log = &self.log;
+		let request = self.requests.get_mut(&id)?;
+
+		if request.response.is_none() {
+			let response = match fixtures.find(&request.method, &request.url) {
+				Some(fixture) => Response {
+					status: fixture.status,
+					headers: fixture.headers.iter()
+						.map(|(name, value)| (name.as_bytes().to_vec(), value.as_bytes().to_vec()))
+						.collect(),
+					body: fixture.body(),
+					read: 0,
+				},
+				None => {
+					log::warn!("No HTTP fixture for {} {}, answering {}", request.method, request.url, NOT_FOUND);
+					Response { status: NOT_FOUND, headers: Vec::new(), body: Vec::new(), read: 0 }
+				},
+			};
+
+			log.lock().expect("HTTP log lock poisoned").push(HttpExchange {
+				method: request.method.clone(),
+				url: request.url.clone(),
+				headers: request.headers.clone(),
+				body: request.body.clone(),
+				status: response.status,
+			});
+			request.response = Some(response);
+		}
+
+		request.response.as_mut()
+	}
+}
+
+fn local_storage_unavailable<R: Default>() -> R {
+	log::error!("The runtime tried to use local offchain storage, which is not available yet");
+	Default::default()
+}
+
+impl<S: OffchainStorage> Externalities for MockExternalities<S> {
+	fn is_validator(&self) -> bool {
+		self.is_validator
+	}
+
+	fn network_state(&self) -> Result<OpaqueNetworkState, ()> {
+		Err(())
+	}
+
+	fn timestamp(&mut self) -> Timestamp {
+		self.timestamp
+	}
+
+	fn sleep_until(&mut self, deadline: Timestamp) {
+		// Sleeping only advances the clock.
+		if deadline.unix_millis() > self.timestamp.unix_millis() {
+			self.timestamp = deadline;
+		}
+	}
+
+	fn random_seed(&mut self) -> [u8; 32] {
+		self.seed = blake2_256(&self.seed);
+		self.seed
+	}
+
+	fn local_storage_set(&mut self, kind: StorageKind, key: &[u8], value: &[u8]) {
+		match kind {
+			StorageKind::PERSISTENT => self.storage.set(sp_offchain::STORAGE_PREFIX, key, value),
+			StorageKind::LOCAL => local_storage_unavailable(),
+		}
+	}
+
+	fn local_storage_clear(&mut self, kind: StorageKind, key: &[u8]) {
+		match kind {
+			StorageKind::PERSISTENT => self.storage.remove(sp_offchain::STORAGE_PREFIX, key),
+			StorageKind::LOCAL => local_storage_unavailable(),
+		}
+	}
+
+	fn local_storage_compare_and_set(
+		&mut self,
+		kind: StorageKind,
+		key: &[u8],
+		old_value: Option<&[u8]>,
+		new_value: &[u8],
+	) -> bool {
+		match kind {
+			StorageKind::PERSISTENT =>
+				self.storage.compare_and_set(sp_offchain::STORAGE_PREFIX, key, old_value, new_value),
+			StorageKind::LOCAL => local_storage_unavailable(),
+		}
+	}
+
+	fn local_storage_get(&mut self, kind: StorageKind, key: &[u8]) -> Option<Vec<u8>> {
+		match kind {
+			StorageKind::PERSISTENT => self.storage.get(sp_offchain::STORAGE_PREFIX, key),
+			StorageKind::LOCAL => local_storage_unavailable(),
+		}
+	}
+
+	fn http_request_start(&mut self, method: &str, uri: &str, _meta: &[u8]) -> Result<HttpRequestId, ()> {
+		let id = self.next_request_id;
+		self.next_request_id = self.next_request_id.checked_add(1).ok_or(())?;
+		self.requests.insert(id, Request {
+			method: method.to_string(),
+			url: uri.to_string(),
+			headers: Vec::new(),
+			body: Vec::new(),
+			response: None,
+		});
+
+		Ok(HttpRequestId(id))
+	}
+
+	fn http_request_add_header(&mut self, request_id: HttpRequestId, name: &str, value: &str) -> Result<(), ()> {
+		match self.requests.get_mut(&request_id.0) {
+			Some(request) if request.response.is_none() => {
+				request.headers.push((name.to_string(), value.to_string()));
+				Ok(())
+			},
+			_ => Err(()),
+		}
+	}
+
+	fn http_request_write_body(
+		&mut self,
+		request_id: HttpRequestId,
+		chunk: &[u8],
+		_deadline: Option<Timestamp>,
+	) -> Result<(), HttpError> {
+		match self.requests.get_mut(&request_id.0) {
+			Some(request) if request.response.is_none() => {
+				request.body.extend_from_slice(chunk);
+				Ok(())
+			},
+			_ => Err(HttpError::Invalid),
+		}
+	}
+
+	fn http_response_wait(
+		&mut self,
+		ids: &[HttpRequestId],
+		_deadline: Option<Timestamp>,
+	) -> Vec<HttpRequestStatus> {
+		ids.iter()
+			.map(|id| match self.respond(id.0) {
+				Some(response) => HttpRequestStatus::Finished(response.status),
+				None => HttpRequestStatus::Invalid,
+			})
+			.collect()
+	}
+
+	fn http_response_headers(&mut self, request_id: HttpRequestId) -> Vec<(Vec<u8>, Vec<u8>)> {
+		self.requests.get(&request_id.0)
+			.and_then(|request| request.response.as_ref())
+			.map(|response| response.headers.clone())
+			.unwrap_or_default()
+	}
+
+	fn http_response_read_body(
+		&mut self,
+		request_id: HttpRequestId,
+		buffer: &mut [u8],
+		_deadline: Option<Timestamp>,
+	) -> Result<usize, HttpError> {
+		let response = self.respond(request_id.0).ok_or(HttpError::Invalid)?;
+		let remaining = &response.body[response.read..];
+		let finished = remaining.is_empty();
+		let len = remaining.len().min(buffer.len());
+		buffer[..len].copy_from_slice(&remaining[..len]);
+		response.read += len;
+
+		if finished {
+			// Like the real HTTP client, forget the request once its body is consumed.
+			self.requests.remove(&request_id.0);
+		}
+
+		Ok(len)
+	}
+
+	fn set_authorized_nodes(&mut self, _nodes: Vec<OpaquePeerId>, _authorized_only: bool) {}
+}
+
+/// Offchain storage reading through to `S` but keeping all writes in memory.
+#[derive(Clone)]
+pub struct DryRunStorage<S> {
+	db: S,
+	changes: Arc<Mutex<BTreeMap<(Vec<u8>, Vec<u8>), Option<Vec<u8>>>>>,
+}
+
+impl<S> DryRunStorage<S> {
+	/// Wrap `db`, which is never written to.
+	pub fn new(db: S) -> Self {
+		DryRunStorage { db, changes: Default::default() }
+	}
+
+	/// Values written (`Some`) or removed (`None`) so far, keyed by prefix and key.
+	pub fn changes(&self) -> BTreeMap<(Vec<u8>, Vec<u8>), Option<Vec<u8>>> {
+		self.changes.lock().expect("offchain changes lock poisoned").clone()
+	}
+
+	fn change(&self, prefix: &[u8], key: &[u8], value: Option<&[u8]>) {
+		self.changes.lock().expect("offchain changes lock poisoned")
+			.insert((prefix.to_vec(), key.to_vec()), value.map(<[u8]>::to_vec));
+	}
+}
+
+impl<S: OffchainStorage> OffchainStorage for DryRunStorage<S> {
+	fn set(&mut self, prefix: &[u8], key: &[u8], value: &[u8]) {
+		self.change(prefix, key, Some(value));
+	}
+
+	fn remove(&mut self, prefix: &[u8], key: &[u8]) {
+		self.change(prefix, key, None);
+	}
+
+	fn get(&self, prefix: &[u8], key: &[u8]) -> Option<Vec<u8>> {
+		let changed = self.changes.lock().expect("offchain changes lock poisoned")
+			.get(&(prefix.to_vec(), key.to_vec()))
+			.cloned();
+
+		changed.unwrap_or_else(|| self.db.get(prefix, key))
+	}
+
+	fn compare_and_set(
+		&mut self,
+		prefix: &[u8],
+		key: &[u8],
+		old_value: Option<&[u8]>,
+		new_value: &[u8],
+	) -> bool {
+		if self.get(prefix, key).as_deref() != old_value {
+			return false;
+		}
+
+		self.change(prefix, key, Some(new_value));
+		true
+	}
+}
+
+/// Transaction pool that keeps the submitted transactions instead of importing them.
+#[derive(Clone, Default)]
+pub struct RecordingPool {
+	submitted: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl RecordingPool {
+	/// Encoded transactions submitted so far.
+	pub fn submitted(&self) -> Vec<Vec<u8>> {
+		self.submitted.lock().expect("submitted transactions lock poisoned").clone()
+	}
+}
+
+impl offchain::TransactionPool for RecordingPool {
+	fn submit_transaction(&mut self, extrinsic: Vec<u8>) -> Result<(), ()> {
+		self.submitted.lock().expect("submitted transactions lock poisoned").push(extrinsic);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn fixture(method: &str, url: Option<&str>, url_prefix: Option<&str>, status: u16) -> Fixture {
+		Fixture {
+			method: method.into(),
+			url: url.map(Into::into),
+			url_prefix: url_prefix.map(Into::into),
+			status,
+			headers: Default::default(),
+			body: serde_json::Value::Null,
+		}
+	}
+
+	fn status(fixtures: &HttpFixtures, method: &str, url: &str) -> Option<u16> {
+		fixtures.find(method, url).map(|fixture| fixture.status)
+	}
+
+	#[test]
+	fn exact_url_beats_any_prefix() {
+		let fixtures = HttpFixtures { fixtures: vec![
+			fixture("GET", None, Some("https://example.com/api?key="), 201),
+			fixture("GET", Some("https://example.com/api?key=1"), None, 202),
+		] };
+
+		assert_eq!(status(&fixtures, "GET", "https://example.com/api?key=1"), Some(202));
+		assert_eq!(status(&fixtures, "GET", "https://example.com/api?key=2"), Some(201));
+	}
+
+	#[test]
+	fn longest_prefix_wins() {
+		let fixtures = HttpFixtures { fixtures: vec![
+			fixture("GET", None, Some("https://example.com/api?module=account&action=balance"), 202),
+			fixture("GET", None, Some("https://example.com/"), 201),
+			fixture("GET", None, Some("https://example.com/api"), 200),
+		] };
+
+		assert_eq!(status(&fixtures, "GET", "https://example.com/api?module=account&action=balancemulti"), Some(202));
+		assert_eq!(status(&fixtures, "GET", "https://example.com/api?module=block"), Some(200));
+		assert_eq!(status(&fixtures, "GET", "https://example.com/"), Some(201));
+		assert_eq!(status(&fixtures, "GET", "https://example.org/"), None);
+	}
+
+	#[test]
+	fn method_must_match() {
+		let fixtures = HttpFixtures { fixtures: vec![
+			fixture("POST", Some("https://example.com/rpc"), None, 201),
+			fixture("GET", None, Some("https://example.com/"), 200),
+		] };
+
+		assert_eq!(status(&fixtures, "post", "https://example.com/rpc"), Some(201));
+		assert_eq!(status(&fixtures, "GET", "https://example.com/rpc"), Some(200));
+		assert_eq!(status(&fixtures, "PUT", "https://example.com/rpc"), None);
+	}
+}
@@ -0,0 +1,127 @@
+//! `ocw-simulate`: run the offchain worker for one block against the local
+//! database and report what it did, without touching the network or the pool.
+
+use std::{path::PathBuf, sync::Arc};
+use codec::Decode;
+use structopt::StructOpt;
+use sc_cli::{BlockNumberOrHash, CliConfiguration, Error, ImportParams, KeystoreParams, SharedParams};
+use sc_client_api::{Backend, ExecutorProvider};
+use sp_blockchain::HeaderBackend;
+use sp_core::hexdisplay::HexDisplay;
+use sp_keystore::SyncCryptoStorePtr;
+use sp_runtime::{generic::BlockId, traits::Header as HeaderT};
+use litentry_runtime::{opaque::Block, UncheckedExtrinsic};
+
+use crate::ocw::{self, mock::{DryRunStorage, HttpFixtures, MockExternalities, RecordingPool}};
+
+#[derive(Debug, StructOpt)]
+pub struct OcwSimulateCmd {
+	/// Block hash or number to run the offchain worker at. Defaults to the best block.
+	#[structopt(value_name = "HASH or NUMBER")]
+	pub block: Option<BlockNumberOrHash>,
+
+	/// Answer HTTP requests from the fixtures in this directory. Without it every
+	/// request is answered with a 404.
+	#[structopt(long = "http-fixtures", value_name = "DIR", parse(from_os_str))]
+	pub http_fixtures: Option<PathBuf>,
+
+	/// Run as a validator would.
+	#[structopt(long)]
+	pub validator: bool,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub import_params: ImportParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub keystore_params: KeystoreParams,
+}
+
+impl OcwSimulateCmd {
+	/// Run the command.
+	pub fn run<C, B>(&self, client: Arc<C>, backend: Arc<B>, keystore: SyncCryptoStorePtr) -> Result<(), Error> where
+		C: HeaderBackend<Block> + ExecutorProvider<Block>,
+		B: Backend<Block>,
+	{
+		let at = match &self.block {
+			Some(block) => block.parse::<Block>()?,
+			None => BlockId::Hash(client.info().best_hash),
+		};
+		let header = client.header(at)?
+			.ok_or_else(|| format!("Block {:?} not found", at))?;
+		let hash = header.hash();
+
+		let fixtures = match &self.http_fixtures {
+			Some(dir) => HttpFixtures::load(dir)?,
+			None => Default::default(),
+		};
+		let storage = DryRunStorage::new(
+			backend.offchain_storage().ok_or("The database has no offchain storage")?,
+		);
+		let offchain = MockExternalities::new(
+			Arc::new(fixtures),
+			storage.clone(),
+			self.validator,
+			ocw::block_timestamp(&*backend, hash)?,
+			hash.as_ref(),
+		);
+		let http_log = offchain.log();
+		let pool = RecordingPool::default();
+
+		println!("Offchain worker at #{} ({})", header.number(), hash);
+		let result = ocw::execute(&*client, &header, offchain, pool.clone(), keystore);
+
+		println!("\nHTTP requests:");
+		for request in http_log.lock().expect("HTTP log lock poisoned").iter() {
+			println!("  {} {} -> {}", request.method, request.url, request.status);
+			for (name, value) in &request.headers {
+				println!("    {}: {}", name, value);
+			}
+			if !request.body.is_empty() {
+				println!("    {}", String::from_utf8_lossy(&request.body));
+			}
+		}
+
+		println!("\nTransactions:");
+		for extrinsic in pool.submitted() {
+			println!("  0x{}", HexDisplay::from(&extrinsic));
+			match UncheckedExtrinsic::decode(&mut &extrinsic[..]) {
+				Ok(decoded) => println!(
+					"    {} {:?}",
+					if decoded.signature.is_some() { "signed" } else { "unsigned" },
+					decoded.function,
+				),
+				Err(e) => println!("    undecodable: {:?}", e),
+			}
+		}
+
+		println!("\nOffchain storage writes (discarded):");
+		for ((_, key), value) in storage.changes() {
+			match value {
+				Some(value) => println!("  {} = 0x{}", String::from_utf8_lossy(&key), HexDisplay::from(&value)),
+				None => println!("  {} removed", String::from_utf8_lossy(&key)),
+			}
+		}
+
+		result.map_err(Error::Client)
+	}
+}
+
+impl CliConfiguration for OcwSimulateCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn import_params(&self) -> Option<&ImportParams> {
+		Some(&self.import_params)
+	}
+
+	fn keystore_params(&self) -> Option<&KeystoreParams> {
+		Some(&self.keystore_params)
+	}
+}
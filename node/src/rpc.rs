@@ -7,54 +7,176 @@
 
 use std::sync::Arc;
 
-use litentry_runtime::{opaque::Block, AccountId, Balance, Index};
+use litentry_runtime::{opaque::Block, AccountId, Balance, BlockNumber, Hash, Index};
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::{Error as BlockChainError, HeaderMetadata, HeaderBackend};
 use sp_block_builder::BlockBuilder;
 pub use sc_rpc_api::DenyUnsafe;
+use sc_rpc::SubscriptionTaskExecutor;
+use sc_finality_grandpa::{
+	SharedVoterState, SharedAuthoritySet, FinalityProofProvider, GrandpaJustificationStream,
+};
+use sc_finality_grandpa_rpc::GrandpaRpcHandler;
 use sp_transaction_pool::TransactionPool;
+use jsonrpc_core::{Error as RpcError, ErrorCode};
 
+pub mod faucet;
+mod light;
+pub mod linker;
+pub mod linker_pubsub;
+pub mod payment;
+
+/// Future returned by the asynchronous node-specific RPC methods.
+pub type FutureResult<T> = Box<dyn jsonrpc_core::futures::Future<Item = T, Error = RpcError> + Send>;
+
+/// Error code for failures reported by the client.
+const CLIENT_ERROR: i64 = 1;
+/// Error code for values that couldn't be decoded.
+const DECODE_ERROR: i64 = 2;
+/// Error code for failures of runtime API calls.
+const RUNTIME_ERROR: i64 = 3;
+/// Error code for faucet drips over a limit.
+const FAUCET_LIMIT_ERROR: i64 = 4;
+/// Error code for faucet drips that couldn't be signed or submitted.
+const FAUCET_ERROR: i64 = 5;
+
+pub(crate) fn client_err(e: sp_blockchain::Error) -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(CLIENT_ERROR),
+		message: "Client error.".into(),
+		data: Some(format!("{:?}", e).into()),
+	}
+}
+
+pub(crate) fn decode_err(e: codec::Error) -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(DECODE_ERROR),
+		message: "Unable to decode value.".into(),
+		data: Some(format!("{:?}", e).into()),
+	}
+}
+
+pub(crate) fn runtime_err(e: sp_api::ApiError) -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(RUNTIME_ERROR),
+		message: "Runtime API error.".into(),
+		data: Some(format!("{:?}", e).into()),
+	}
+}
+
+/// Extra dependencies for GRANDPA
+pub struct GrandpaDeps<B> {
+	/// Voting round info.
+	pub shared_voter_state: SharedVoterState,
+	/// Authority set info.
+	pub shared_authority_set: SharedAuthoritySet<Hash, BlockNumber>,
+	/// Receives notifications about justification events from Grandpa.
+	pub justification_stream: GrandpaJustificationStream<Block>,
+	/// Executor to drive the subscription manager in the Grandpa RPC handler.
+	pub subscription_executor: SubscriptionTaskExecutor,
+	/// Finality proof provider.
+	pub finality_provider: Arc<FinalityProofProvider<B, Block>>,
+}
 
 /// Full client dependencies.
-pub struct FullDeps<C, P> {
+pub struct FullDeps<C, P, B> {
 	/// The client instance to use.
 	pub client: Arc<C>,
 	/// Transaction pool instance.
 	pub pool: Arc<P>,
 	/// Whether to deny unsafe calls
 	pub deny_unsafe: DenyUnsafe,
+	/// GRANDPA specific dependencies.
+	pub grandpa: GrandpaDeps<B>,
+	/// The faucet to serve `faucet_drip` from, if any.
+	pub faucet: Option<Arc<faucet::FaucetAccount>>,
+}
+
+/// Light client dependencies.
+pub struct LightDeps<C, F, P> {
+	/// The client instance to use.
+	pub client: Arc<C>,
+	/// Transaction pool instance.
+	pub pool: Arc<P>,
+	/// Remote access to the blockchain (async).
+	pub remote_blockchain: Arc<dyn sc_client_api::light::RemoteBlockchain<Block>>,
+	/// Fetcher instance.
+	pub fetcher: Arc<F>,
 }
 
 /// Instantiate all full RPC extensions.
-pub fn create_full<C, P>(
-	deps: FullDeps<C, P>,
+pub fn create_full<C, P, B>(
+	deps: FullDeps<C, P, B>,
 ) -> jsonrpc_core::IoHandler<sc_rpc::Metadata> where
 	C: ProvideRuntimeApi<Block>,
 	C: HeaderBackend<Block> + HeaderMetadata<Block, Error=BlockChainError> + 'static,
+	C: sc_client_api::StorageProvider<Block, B>,
+	C: sc_client_api::BlockchainEvents<Block>,
 	C: Send + Sync + 'static,
 	C::Api: substrate_frame_rpc_system::AccountNonceApi<Block, AccountId, Index>,
 	C::Api: pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi<Block, Balance>,
 	C::Api: BlockBuilder<Block>,
-	P: TransactionPool + 'static,
+	C::Api: litentry_runtime::link_index::LinkIndexApi<Block, AccountId>,
+	C::Api: sp_api::Metadata<Block>,
+	P: TransactionPool<Block = Block, Hash = Hash> + 'static,
+	B: sc_client_api::Backend<Block> + Send + Sync + 'static,
+	B::State: sc_client_api::StateBackend<sp_runtime::traits::HashFor<Block>>,
 {
 	use substrate_frame_rpc_system::{FullSystem, SystemApi};
 	use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApi};
+	use sc_finality_grandpa_rpc::GrandpaApi;
+	use linker::{Linker, LinkerApi};
+	use linker_pubsub::{LinkerPubSub, LinkerPubSubApi};
+	use faucet::{Faucet, FaucetApi};
 
 	let mut io = jsonrpc_core::IoHandler::default();
 	let FullDeps {
 		client,
 		pool,
 		deny_unsafe,
+		grandpa,
+		faucet,
 	} = deps;
+	let GrandpaDeps {
+		shared_voter_state,
+		shared_authority_set,
+		justification_stream,
+		subscription_executor,
+		finality_provider,
+	} = grandpa;
 
 	io.extend_with(
-		SystemApi::to_delegate(FullSystem::new(client.clone(), pool, deny_unsafe))
+		SystemApi::to_delegate(FullSystem::new(client.clone(), pool.clone(), deny_unsafe))
 	);
 
 	io.extend_with(
 		TransactionPaymentApi::to_delegate(TransactionPayment::new(client.clone()))
 	);
 
+	io.extend_with(
+		GrandpaApi::to_delegate(GrandpaRpcHandler::new(
+			shared_authority_set,
+			shared_voter_state,
+			justification_stream,
+			subscription_executor.clone(),
+			finality_provider,
+		))
+	);
+
+	io.extend_with(
+		LinkerApi::to_delegate(Linker::new(client.clone()))
+	);
+
+	io.extend_with(
+		LinkerPubSubApi::to_delegate(LinkerPubSub::new(client.clone(), subscription_executor))
+	);
+
+	if let Some(faucet) = faucet {
+		io.extend_with(
+			FaucetApi::to_delegate(Faucet::new(client.clone(), pool, deny_unsafe, faucet))
+		);
+	}
+
 	// Extend this RPC with a custom API by using the following syntax.
 	// `YourRpcStruct` should have a reference to a client, which is needed
 	// to call into the runtime.
@@ -62,3 +184,44 @@ pub fn create_full<C, P>(
 
 	io
 }
+
+/// Instantiate all light RPC extensions.
+pub fn create_light<C, P, M, F>(
+	deps: LightDeps<C, F, P>,
+) -> jsonrpc_core::IoHandler<M> where
+	C: HeaderBackend<Block>,
+	C: Send + Sync + 'static,
+	F: sc_client_api::light::Fetcher<Block> + 'static,
+	P: TransactionPool + 'static,
+	M: jsonrpc_core::Metadata + Default,
+{
+	use substrate_frame_rpc_system::{LightSystem, SystemApi};
+	use payment::{LightTransactionPayment, LightTransactionPaymentApi};
+	use linker::{LightLinker, LinkerApi};
+
+	let LightDeps {
+		client,
+		pool,
+		remote_blockchain,
+		fetcher,
+	} = deps;
+	let mut io = jsonrpc_core::IoHandler::default();
+
+	io.extend_with(
+		SystemApi::<Hash, AccountId, Index>::to_delegate(
+			LightSystem::new(client.clone(), remote_blockchain.clone(), fetcher.clone(), pool)
+		)
+	);
+
+	io.extend_with(
+		LightTransactionPaymentApi::to_delegate(
+			LightTransactionPayment::new(client.clone(), remote_blockchain.clone(), fetcher.clone())
+		)
+	);
+
+	io.extend_with(
+		LinkerApi::to_delegate(LightLinker::new(client, remote_blockchain, fetcher))
+	);
+
+	io
+}
@@ -0,0 +1,409 @@
+//! `faucet_drip`: transfer test tokens from an account in the node's keystore.
+//!
+//! Only for development and local chains. An unsafe method on the node's RPC
+//! servers, limited per account and per UTC day. The node's RPC servers don't
+//! tell methods the caller's address, so drips requested through
+//! [`crate::faucet_endpoint`] are also limited per IP.
+
+use std::{
+	collections::HashMap,
+	net::IpAddr,
+	sync::{Arc, Mutex},
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use codec::{Decode, Encode};
+use futures::{lock::Mutex as AsyncMutex, FutureExt, TryFutureExt};
+use jsonrpc_core::{Error as RpcError, ErrorCode};
+use jsonrpc_derive::rpc;
+use litentry_runtime::{opaque::Block, AccountId, Balance, Call, Hash, Index, Runtime, SignedExtra, SignedPayload, UncheckedExtrinsic};
+use sp_api::{Core, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_core::{crypto::key_types, sr25519};
+use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
+use sp_runtime::{generic::{BlockId, Era}, OpaqueExtrinsic};
+use sp_transaction_pool::{InPoolTransaction, TransactionPool, TransactionSource};
+use substrate_frame_rpc_system::AccountNonceApi;
+
+use super::{decode_err, runtime_err, DenyUnsafe, FutureResult, FAUCET_ERROR, FAUCET_LIMIT_ERROR};
+
+/// Length of the period `daily_cap` applies to.
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn limit_err(message: String) -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(FAUCET_LIMIT_ERROR),
+		message: "Faucet limit reached.".into(),
+		data: Some(message.into()),
+	}
+}
+
+fn drip_err(message: String) -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(FAUCET_ERROR),
+		message: "Faucet transfer failed.".into(),
+		data: Some(message.into()),
+	}
+}
+
+/// Testnet faucet.
+#[rpc]
+pub trait FaucetApi<BlockHash> {
+	/// Transfer the faucet amount to `account`. Returns the transaction hash.
+	#[rpc(name = "faucet_drip")]
+	fn drip(&self, account: AccountId) -> FutureResult<BlockHash>;
+}
+
+/// What the faucet sends, from where, and how often.
+#[derive(Debug, Clone)]
+pub struct FaucetConfig {
+	/// The `acco` key in the keystore to send from.
+	pub account: sr25519::Public,
+	/// Amount of each drip.
+	pub amount: Balance,
+	/// Shortest time between two drips to the same account.
+	pub interval: Duration,
+	/// Shortest time between two drips requested from the same IP, where known.
+	pub ip_interval: Duration,
+	/// Most sent per UTC day.
+	pub daily_cap: Balance,
+}
+
+/// Drips so far, to enforce the limits.
+#[derive(Debug, Default)]
+struct Limits {
+	/// Time of the last drip to each account within the interval.
+	last_drips: HashMap<AccountId, Instant>,
+	/// Time of the last drip requested from each IP within the IP interval.
+	last_ip_drips: HashMap<IpAddr, Instant>,
+	/// UTC day `dripped` counts.
+	day: u64,
+	/// Amount sent on `day`.
+	dripped: Balance,
+}
+
+impl Limits {
+	/// Count a drip to `account` requested from `ip`, if within the limits.
+	fn reserve(
+		&mut self,
+		config: &FaucetConfig,
+		account: &AccountId,
+		ip: Option<IpAddr>,
+		now: Instant,
+		day: u64,
+	) -> Result<(), String> {
+		self.last_drips.retain(|_, last| now.duration_since(*last) < config.interval);
+		self.last_ip_drips.retain(|_, last| now.duration_since(*last) < config.ip_interval);
+		if let Some(last) = self.last_drips.get(account) {
+			let wait = config.interval - now.duration_since(*last);
+			return Err(format!("{} got tokens recently, try again in {}s", account, wait.as_secs() + 1));
+		}
+		if let Some((ip, last)) = ip.and_then(|ip| Some((ip, self.last_ip_drips.get(&ip)?))) {
+			let wait = config.ip_interval - now.duration_since(*last);
+			return Err(format!("{} requested tokens recently, try again in {}s", ip, wait.as_secs() + 1));
+		}
+
+		if self.day != day {
+			self.day = day;
+			self.dripped = 0;
+		}
+		if self.dripped.saturating_add(config.amount) > config.daily_cap {
+			return Err(format!("The daily cap of {} is used up, try again tomorrow (UTC)", config.daily_cap));
+		}
+
+		self.last_drips.insert(account.clone(), now);
+		if let Some(ip) = ip {
+			self.last_ip_drips.insert(ip, now);
+		}
+		self.dripped += config.amount;
+		Ok(())
+	}
+
+	/// Undo [`Limits::reserve`] for a drip that wasn't submitted.
+	fn release(&mut self, config: &FaucetConfig, account: &AccountId, ip: Option<IpAddr>, day: u64) {
+		self.last_drips.remove(account);
+		if let Some(ip) = ip {
+			self.last_ip_drips.remove(&ip);
+		}
+		if self.day == day {
+			self.dripped = self.dripped.saturating_sub(config.amount);
+		}
+	}
+}
+
+/// The faucet account and its limits.
+pub struct FaucetAccount {
+	config: FaucetConfig,
+	keystore: SyncCryptoStorePtr,
+	limits: Mutex<Limits>,
+	/// Held from computing the nonce of a transfer until the pool took it, so
+	/// concurrent drips don't sign the same nonce.
+	submission: AsyncMutex<()>,
+}
+
+impl FaucetAccount {
+	/// Create the faucet, failing if the keystore lacks its key.
+	pub fn new(config: FaucetConfig, keystore: SyncCryptoStorePtr) -> Result<Self, String> {
+		if !SyncCryptoStore::has_keys(&*keystore, &[(config.account.0.to_vec(), key_types::ACCOUNT)]) {
+			return Err(format!(
+				"The keystore has no `acco` key {} for the faucet, insert it with `key insert --key-type acco`",
+				AccountId::from(config.account),
+			));
+		}
+
+		Ok(FaucetAccount { config, keystore, limits: Default::default(), submission: AsyncMutex::new(()) })
+	}
+}
+
+/// UTC days since the Unix epoch.
+fn today() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / DAY.as_secs()
+}
+
+/// Full client implementation of [`FaucetApi`].
+pub struct Faucet<C, P> {
+	client: Arc<C>,
+	pool: Arc<P>,
+	deny_unsafe: DenyUnsafe,
+	account: Arc<FaucetAccount>,
+}
+
+impl<C, P> Faucet<C, P> {
+	/// Create a new `Faucet`.
+	pub fn new(client: Arc<C>, pool: Arc<P>, deny_unsafe: DenyUnsafe, account: Arc<FaucetAccount>) -> Self {
+		Faucet { client, pool, deny_unsafe, account }
+	}
+}
+
+impl<C, P> Clone for Faucet<C, P> {
+	fn clone(&self) -> Self {
+		Faucet {
+			client: self.client.clone(),
+			pool: self.pool.clone(),
+			deny_unsafe: self.deny_unsafe,
+			account: self.account.clone(),
+		}
+	}
+}
+
+impl<C, P> Faucet<C, P> where
+	C: HeaderBackend<Block> + ProvideRuntimeApi<Block>,
+	C::Api: AccountNonceApi<Block, AccountId, Index> + Core<Block>,
+	P: TransactionPool<Block = Block>,
+{
+	/// Next nonce of `account`, counting its transactions in the pool.
+	fn nonce(&self, at: &BlockId<Block>, account: &AccountId) -> jsonrpc_core::Result<Index> {
+		let mut nonce = self.client.runtime_api().account_nonce(at, account.clone()).map_err(runtime_err)?;
+		for tx in self.pool.ready() {
+			if tx.provides().get(0) == Some(&(account, nonce).encode()) {
+				nonce += 1;
+			}
+		}
+		Ok(nonce)
+	}
+
+	/// A signed transfer of the drip amount to `dest`.
+	fn transfer(&self, dest: AccountId) -> jsonrpc_core::Result<(BlockId<Block>, OpaqueExtrinsic)> {
+		let info = self.client.info();
+		let at = BlockId::Hash(info.best_hash);
+		let version = self.client.runtime_api().version(&at).map_err(runtime_err)?;
+		let config = &self.account.config;
+		let from = AccountId::from(config.account);
+		let nonce = self.nonce(&at, &from)?;
+
+		let call = Call::Balances(pallet_balances::Call::transfer(dest, config.amount));
+		let extra: SignedExtra = (
+			frame_system::CheckSpecVersion::<Runtime>::new(),
+			frame_system::CheckTxVersion::<Runtime>::new(),
+			frame_system::CheckGenesis::<Runtime>::new(),
+			frame_system::CheckEra::<Runtime>::from(Era::Immortal),
+			frame_system::CheckNonce::<Runtime>::from(nonce),
+			frame_system::CheckWeight::<Runtime>::new(),
+			pallet_transaction_payment::ChargeTransactionPayment::<Runtime>::from(0),
+		);
+		let payload = SignedPayload::from_raw(
+			call,
+			extra,
+			(
+				version.spec_version,
+				version.transaction_version,
+				info.genesis_hash,
+				info.genesis_hash,
+				(),
+				(),
+				(),
+			),
+		);
+
+		let signature = payload.using_encoded(|message| SyncCryptoStore::sign_with(
+			&*self.account.keystore,
+			key_types::ACCOUNT,
+			&config.account.into(),
+			message,
+		)).map_err(|e| drip_err(format!("Keystore failed to sign: {:?}", e)))?;
+		let (call, extra, _) = payload.deconstruct();
+		let extrinsic = UncheckedExtrinsic::new_signed(
+			call,
+			from,
+			sr25519::Signature::from_slice(&signature).into(),
+			extra,
+		);
+
+		let extrinsic = OpaqueExtrinsic::decode(&mut &extrinsic.encode()[..]).map_err(decode_err)?;
+		Ok((at, extrinsic))
+	}
+}
+
+impl<C, P> Faucet<C, P> where
+	C: HeaderBackend<Block> + ProvideRuntimeApi<Block> + Send + Sync + 'static,
+	C::Api: AccountNonceApi<Block, AccountId, Index> + Core<Block>,
+	P: TransactionPool<Block = Block, Hash = Hash> + 'static,
+{
+	/// Transfer the faucet amount to `account` for a caller at `ip`, if known,
+	/// without checking whether unsafe methods are allowed.
+	pub fn drip_from(&self, ip: Option<IpAddr>, account: AccountId) -> FutureResult<Hash> {
+		let day = today();
+		let reserved = self.account.limits.lock().expect("no panics while locked; qed")
+			.reserve(&self.account.config, &account, ip, Instant::now(), day);
+		if let Err(e) = reserved {
+			return Box::new(jsonrpc_core::futures::future::err(limit_err(e)));
+		}
+
+		let release = {
+			let faucet = self.account.clone();
+			let account = account.clone();
+			move || faucet.limits.lock().expect("no panics while locked; qed")
+				.release(&faucet.config, &account, ip, day)
+		};
+		let faucet = self.clone();
+		let future = async move {
+			let _submission = faucet.account.submission.lock().await;
+			let (at, extrinsic) = faucet.transfer(account)?;
+			faucet.pool.submit_one(&at, TransactionSource::Local, extrinsic).await
+				.map_err(|e| drip_err(format!("Transaction pool rejected the transfer: {}", e)))
+		}.map(move |result| {
+			if result.is_err() {
+				release();
+			}
+			result
+		});
+
+		Box::new(future.boxed().compat())
+	}
+}
+
+impl<C, P> FaucetApi<Hash> for Faucet<C, P> where
+	C: HeaderBackend<Block> + ProvideRuntimeApi<Block> + Send + Sync + 'static,
+	C::Api: AccountNonceApi<Block, AccountId, Index> + Core<Block>,
+	P: TransactionPool<Block = Block, Hash = Hash> + 'static,
+{
+	fn drip(&self, account: AccountId) -> FutureResult<Hash> {
+		if let Err(e) = self.deny_unsafe.check_if_safe() {
+			return Box::new(jsonrpc_core::futures::future::err(e.into()));
+		}
+
+		self.drip_from(None, account)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const AMOUNT: Balance = 10;
+
+	fn config() -> FaucetConfig {
+		FaucetConfig {
+			account: sr25519::Public::from_raw([0; 32]),
+			amount: AMOUNT,
+			interval: Duration::from_secs(60),
+			ip_interval: Duration::from_secs(10),
+			daily_cap: 3 * AMOUNT,
+		}
+	}
+
+	fn account(byte: u8) -> AccountId {
+		AccountId::new([byte; 32])
+	}
+
+	fn ip(byte: u8) -> Option<IpAddr> {
+		Some([10, 0, 0, byte].into())
+	}
+
+	#[test]
+	fn account_waits_for_interval() {
+		let config = config();
+		let mut limits = Limits::default();
+		let start = Instant::now();
+
+		assert_eq!(limits.reserve(&config, &account(1), ip(1), start, 0), Ok(()));
+		assert!(limits.reserve(&config, &account(1), ip(2), start + Duration::from_secs(59), 0).is_err());
+		assert_eq!(limits.reserve(&config, &account(1), ip(3), start + Duration::from_secs(60), 0), Ok(()));
+	}
+
+	#[test]
+	fn ip_waits_for_ip_interval() {
+		let config = config();
+		let mut limits = Limits::default();
+		let start = Instant::now();
+
+		assert_eq!(limits.reserve(&config, &account(1), ip(1), start, 0), Ok(()));
+		let refused = limits.reserve(&config, &account(2), ip(1), start + Duration::from_secs(9), 0);
+		assert_eq!(refused, Err("10.0.0.1 requested tokens recently, try again in 2s".to_string()));
+		assert_eq!(limits.reserve(&config, &account(2), ip(1), start + Duration::from_secs(10), 0), Ok(()));
+	}
+
+	#[test]
+	fn daily_cap_resets_next_day() {
+		let config = config();
+		let mut limits = Limits::default();
+		let now = Instant::now();
+
+		for byte in 1..=3 {
+			assert_eq!(limits.reserve(&config, &account(byte), ip(byte), now, 0), Ok(()));
+		}
+		assert!(limits.reserve(&config, &account(4), ip(4), now, 0).is_err());
+		assert_eq!(limits.dripped, 3 * AMOUNT);
+
+		assert_eq!(limits.reserve(&config, &account(4), ip(4), now, 1), Ok(()));
+		assert_eq!((limits.day, limits.dripped), (1, AMOUNT));
+	}
+
+	#[test]
+	fn release_undoes_failed_drip() {
+		let config = config();
+		let mut limits = Limits::default();
+		let now = Instant::now();
+
+		for byte in 1..=3 {
+			assert_eq!(limits.reserve(&config, &account(byte), ip(byte), now, 0), Ok(()));
+		}
+		limits.release(&config, &account(3), ip(3), 0);
+		assert_eq!(limits.dripped, 2 * AMOUNT);
+		assert_eq!(limits.reserve(&config, &account(3), ip(3), now, 0), Ok(()));
+	}
+
+	#[test]
+	fn release_after_day_rollover_keeps_new_day() {
+		let config = config();
+		let mut limits = Limits::default();
+		let now = Instant::now();
+
+		assert_eq!(limits.reserve(&config, &account(1), ip(1), now, 0), Ok(()));
+		assert_eq!(limits.reserve(&config, &account(2), ip(2), now, 1), Ok(()));
+		limits.release(&config, &account(1), ip(1), 0);
+		assert_eq!((limits.day, limits.dripped), (1, AMOUNT));
+	}
+
+	#[test]
+	fn unknown_ip_is_only_limited_per_account() {
+		let config = config();
+		let mut limits = Limits::default();
+		let now = Instant::now();
+
+		assert_eq!(limits.reserve(&config, &account(1), None, now, 0), Ok(()));
+		assert_eq!(limits.reserve(&config, &account(2), None, now, 0), Ok(()));
+		assert!(limits.reserve(&config, &account(2), None, now, 0).is_err());
+		assert!(limits.last_ip_drips.is_empty());
+	}
+}
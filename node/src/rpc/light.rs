@@ -0,0 +1,51 @@
+//! Helpers for answering RPC queries on a light client by asking full peers.
+
+use std::sync::Arc;
+
+use futures::{future::{ready, Future}, TryFutureExt};
+use litentry_runtime::{opaque::{Block, Header}, Hash};
+use sc_client_api::light::{
+	future_header, Fetcher, RemoteBlockchain, RemoteCallRequest,
+};
+use sp_blockchain::Error as ClientError;
+use sp_runtime::generic::BlockId;
+
+/// Fetch the header of `at`, or of `best_hash` when `at` is not given.
+pub fn remote_header<F: Fetcher<Block>>(
+	best_hash: Hash,
+	remote_blockchain: &dyn RemoteBlockchain<Block>,
+	fetcher: &F,
+	at: Option<Hash>,
+) -> impl Future<Output = Result<(Hash, Header), ClientError>> {
+	let hash = at.unwrap_or(best_hash);
+
+	future_header(remote_blockchain, fetcher, BlockId::Hash(hash))
+		.and_then(move |header| ready(
+			header
+				.map(|header| (hash, header))
+				.ok_or_else(|| ClientError::UnknownBlock(format!("{}", hash)))
+		))
+}
+
+/// Call a runtime API `method` at `at` (or at the best block) on full peers.
+pub fn remote_call<F: Fetcher<Block> + 'static>(
+	best_hash: Hash,
+	remote_blockchain: &dyn RemoteBlockchain<Block>,
+	fetcher: Arc<F>,
+	at: Option<Hash>,
+	method: &str,
+	call_data: Vec<u8>,
+) -> impl Future<Output = Result<Vec<u8>, ClientError>> {
+	let method = method.to_string();
+
+	remote_header(best_hash, remote_blockchain, &*fetcher, at)
+		.and_then(move |(block, header)| {
+			fetcher.remote_call(RemoteCallRequest {
+				block,
+				header,
+				method,
+				call_data,
+				retry_count: None,
+			})
+		})
+}
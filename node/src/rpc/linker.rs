@@ -0,0 +1,165 @@
+//! RPC methods for querying the links kept by `AccountLinkerModule`, and the
+//! accounts that linked an address, through the runtime's `LinkIndexApi`.
+
+use std::sync::Arc;
+
+use codec::{Decode, Encode};
+use futures::{FutureExt, TryFutureExt};
+use jsonrpc_derive::rpc;
+use litentry_runtime::{link_index::LinkIndexApi, opaque::Block, AccountId, Hash};
+use sc_client_api::light::{Fetcher, RemoteBlockchain};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::{Bytes, H160};
+use sp_runtime::generic::BlockId;
+
+use super::{client_err, decode_err, light::remote_call, runtime_err, FutureResult};
+
+pub use self::gen_client::Client as LinkerClient;
+
+/// Queries over the Ethereum and Bitcoin addresses linked to Litentry accounts.
+#[rpc]
+pub trait LinkerApi<BlockHash> {
+	/// Ethereum addresses linked to `account`.
+	#[rpc(name = "linker_ethereumLinks")]
+	fn ethereum_links(&self, account: AccountId, at: Option<BlockHash>) -> FutureResult<Vec<H160>>;
+
+	/// Bitcoin addresses linked to `account`.
+	#[rpc(name = "linker_bitcoinLinks")]
+	fn bitcoin_links(&self, account: AccountId, at: Option<BlockHash>) -> FutureResult<Vec<Bytes>>;
+
+	/// Accounts that linked the Ethereum address `address`.
+	#[rpc(name = "linker_ethereumOwners")]
+	fn ethereum_owners(&self, address: H160, at: Option<BlockHash>) -> FutureResult<Vec<AccountId>>;
+
+	/// Accounts that linked the Bitcoin address `address`.
+	#[rpc(name = "linker_bitcoinOwners")]
+	fn bitcoin_owners(&self, address: Bytes, at: Option<BlockHash>) -> FutureResult<Vec<AccountId>>;
+}
+
+pub(crate) fn ethereum_links(addresses: Vec<[u8; 20]>) -> Vec<H160> {
+	addresses.into_iter().map(H160::from).collect()
+}
+
+pub(crate) fn bitcoin_links(addresses: Vec<Vec<u8>>) -> Vec<Bytes> {
+	addresses.into_iter().map(Bytes).collect()
+}
+
+/// Full client implementation of [`LinkerApi`], calling the local runtime.
+pub struct Linker<C> {
+	client: Arc<C>,
+}
+
+impl<C> Linker<C> {
+	/// Create a new `Linker`.
+	pub fn new(client: Arc<C>) -> Self {
+		Linker { client }
+	}
+}
+
+impl<C> Linker<C> where
+	C: HeaderBackend<Block> + ProvideRuntimeApi<Block>,
+	C::Api: LinkIndexApi<Block, AccountId>,
+{
+	fn call<T, R>(
+		&self,
+		at: Option<Hash>,
+		call: impl FnOnce(&C::Api, &BlockId<Block>) -> Result<T, sp_api::ApiError>,
+		map: impl FnOnce(T) -> R,
+	) -> FutureResult<R> where R: Send + 'static {
+		let at = BlockId::Hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		let result = call(&*self.client.runtime_api(), &at)
+			.map(map)
+			.map_err(runtime_err);
+
+		Box::new(jsonrpc_core::futures::future::result(result))
+	}
+}
+
+impl<C> LinkerApi<Hash> for Linker<C> where
+	C: HeaderBackend<Block> + ProvideRuntimeApi<Block> + Send + Sync + 'static,
+	C::Api: LinkIndexApi<Block, AccountId>,
+{
+	fn ethereum_links(&self, account: AccountId, at: Option<Hash>) -> FutureResult<Vec<H160>> {
+		self.call(at, |api, at| api.ethereum_links(at, account), ethereum_links)
+	}
+
+	fn bitcoin_links(&self, account: AccountId, at: Option<Hash>) -> FutureResult<Vec<Bytes>> {
+		self.call(at, |api, at| api.bitcoin_links(at, account), bitcoin_links)
+	}
+
+	fn ethereum_owners(&self, address: H160, at: Option<Hash>) -> FutureResult<Vec<AccountId>> {
+		self.call(at, |api, at| api.ethereum_owners(at, address.0), |owners| owners)
+	}
+
+	fn bitcoin_owners(&self, address: Bytes, at: Option<Hash>) -> FutureResult<Vec<AccountId>> {
+		self.call(at, |api, at| api.bitcoin_owners(at, address.0), |owners| owners)
+	}
+}
+
+/// Light client implementation of [`LinkerApi`], reading the state from full peers.
+pub struct LightLinker<C, F> {
+	client: Arc<C>,
+	remote_blockchain: Arc<dyn RemoteBlockchain<Block>>,
+	fetcher: Arc<F>,
+}
+
+impl<C, F> LightLinker<C, F> {
+	/// Create a new `LightLinker`.
+	pub fn new(
+		client: Arc<C>,
+		remote_blockchain: Arc<dyn RemoteBlockchain<Block>>,
+		fetcher: Arc<F>,
+	) -> Self {
+		LightLinker { client, remote_blockchain, fetcher }
+	}
+}
+
+impl<C, F> LightLinker<C, F> where
+	C: HeaderBackend<Block>,
+	F: Fetcher<Block> + 'static,
+{
+	fn call<T: Decode + 'static, R: Send + 'static>(
+		&self,
+		method: &str,
+		call_data: Vec<u8>,
+		at: Option<Hash>,
+		map: fn(T) -> R,
+	) -> FutureResult<R> {
+		let future = remote_call(
+			self.client.info().best_hash,
+			&*self.remote_blockchain,
+			self.fetcher.clone(),
+			at,
+			method,
+			call_data,
+		).map(move |result| result
+			.map_err(client_err)
+			.and_then(|value| T::decode(&mut &value[..]).map_err(decode_err))
+			.map(map)
+		);
+
+		Box::new(future.boxed().compat())
+	}
+}
+
+impl<C, F> LinkerApi<Hash> for LightLinker<C, F> where
+	C: HeaderBackend<Block> + Send + Sync + 'static,
+	F: Fetcher<Block> + 'static,
+{
+	fn ethereum_links(&self, account: AccountId, at: Option<Hash>) -> FutureResult<Vec<H160>> {
+		self.call("LinkIndexApi_ethereum_links", account.encode(), at, ethereum_links)
+	}
+
+	fn bitcoin_links(&self, account: AccountId, at: Option<Hash>) -> FutureResult<Vec<Bytes>> {
+		self.call("LinkIndexApi_bitcoin_links", account.encode(), at, bitcoin_links)
+	}
+
+	fn ethereum_owners(&self, address: H160, at: Option<Hash>) -> FutureResult<Vec<AccountId>> {
+		self.call("LinkIndexApi_ethereum_owners", address.0.encode(), at, |owners| owners)
+	}
+
+	fn bitcoin_owners(&self, address: Bytes, at: Option<Hash>) -> FutureResult<Vec<AccountId>> {
+		self.call("LinkIndexApi_bitcoin_owners", address.0.encode(), at, |owners| owners)
+	}
+}
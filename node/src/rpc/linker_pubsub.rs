@@ -0,0 +1,229 @@
+//! `linker_subscribeLinks`: notifications of `AccountLinkerModule` events about
+//! watched accounts or addresses, from new best or finalized blocks.
+
+use std::{marker::PhantomData, sync::Arc};
+
+use futures::{future, StreamExt, TryStreamExt};
+use jsonrpc_core::futures::{future::Future as Future01, sink::Sink as Sink01, stream::Stream as Stream01};
+use jsonrpc_derive::rpc;
+use jsonrpc_pubsub::{manager::SubscriptionManager, typed::Subscriber, SubscriptionId};
+use litentry_runtime::{link_index::LinkIndexApi, opaque::Block, AccountId, Hash};
+use sc_client_api::{Backend, BlockchainEvents, StorageProvider};
+use sc_rpc::SubscriptionTaskExecutor;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sp_api::{Metadata as MetadataApi, ProvideRuntimeApi};
+use sp_core::{crypto::Ss58Codec, hashing::twox_128, storage::{well_known_keys, StorageKey}, Bytes, H160};
+use sp_runtime::{generic::BlockId, traits::Header as HeaderT};
+
+use crate::scale::{self, Metadata};
+use super::linker::{bitcoin_links, ethereum_links};
+
+/// What to be notified about. Without accounts and addresses, every link is.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct LinkFilter {
+	/// Accounts whose links to watch.
+	#[serde(default)]
+	pub accounts: Vec<AccountId>,
+	/// Ethereum addresses, or Bitcoin addresses as the bytes of their string, to watch.
+	#[serde(default)]
+	pub addresses: Vec<Bytes>,
+	/// Notify about finalized blocks instead of new best blocks.
+	#[serde(default)]
+	pub finalized: bool,
+}
+
+impl LinkFilter {
+	/// Whether to notify about an event of `account` with `args`, which is linked
+	/// to `ethereum` and `bitcoin` after the event. The event's arguments cover
+	/// addresses a relink replaced.
+	fn matches(&self, account: &AccountId, args: &Value, ethereum: &[H160], bitcoin: &[Bytes]) -> bool {
+		(self.accounts.is_empty() && self.addresses.is_empty())
+			|| self.accounts.contains(account)
+			|| self.addresses.iter().any(|address| {
+				let hex = format!("0x{}", hex::encode(&address[..]));
+				ethereum.iter().any(|linked| linked.as_bytes() == &address[..])
+					|| bitcoin.contains(address)
+					|| args.as_array().into_iter().flatten().any(|arg| arg.as_str().map_or(false, |arg| arg.eq_ignore_ascii_case(&hex)))
+			})
+	}
+}
+
+/// An `AccountLinkerModule` event and the links of its account after the block.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkNotification {
+	/// Block the event was emitted in.
+	pub block_hash: Hash,
+	/// Number of that block.
+	pub block_number: u32,
+	/// Name of the event.
+	pub event: String,
+	/// Arguments of the event, formatted like the `decode` subcommand prints them.
+	pub args: Value,
+	/// Account the event is about.
+	pub account: AccountId,
+	/// Ethereum addresses linked to `account`.
+	pub ethereum_links: Vec<H160>,
+	/// Bitcoin addresses linked to `account`.
+	pub bitcoin_links: Vec<Bytes>,
+}
+
+/// Subscriptions to link events.
+#[rpc(server)]
+pub trait LinkerPubSubApi {
+	/// RPC metadata.
+	type Metadata;
+
+	/// Notify about link events matching `filter`.
+	#[pubsub(subscription = "linker_links", subscribe, name = "linker_subscribeLinks")]
+	fn subscribe_links(&self, metadata: Self::Metadata, subscriber: Subscriber<LinkNotification>, filter: LinkFilter);
+
+	/// Cancel a subscription.
+	#[pubsub(subscription = "linker_links", unsubscribe, name = "linker_unsubscribeLinks")]
+	fn unsubscribe_links(&self, metadata: Option<Self::Metadata>, id: SubscriptionId) -> jsonrpc_core::Result<bool>;
+}
+
+/// Full client implementation of [`LinkerPubSubApi`].
+pub struct LinkerPubSub<C, B> {
+	client: Arc<C>,
+	manager: SubscriptionManager,
+	_marker: PhantomData<B>,
+}
+
+impl<C, B> LinkerPubSub<C, B> {
+	/// Create a new `LinkerPubSub`.
+	pub fn new(client: Arc<C>, executor: SubscriptionTaskExecutor) -> Self {
+		LinkerPubSub { client, manager: SubscriptionManager::new(Arc::new(executor)), _marker: Default::default() }
+	}
+}
+
+impl<C, B> LinkerPubSubApi for LinkerPubSub<C, B> where
+	C: StorageProvider<Block, B> + BlockchainEvents<Block> + ProvideRuntimeApi<Block> + Send + Sync + 'static,
+	C::Api: MetadataApi<Block> + LinkIndexApi<Block, AccountId>,
+	B: Backend<Block> + Send + Sync + 'static,
+{
+	type Metadata = sc_rpc::Metadata;
+
+	fn subscribe_links(&self, _metadata: Self::Metadata, subscriber: Subscriber<LinkNotification>, filter: LinkFilter) {
+		let blocks = if filter.finalized {
+			self.client.finality_notification_stream()
+				.map(|notification| (notification.hash, *notification.header.number()))
+				.boxed()
+		} else {
+			self.client.import_notification_stream()
+				.filter(|notification| future::ready(notification.is_new_best))
+				.map(|notification| (notification.hash, *notification.header.number()))
+				.boxed()
+		};
+
+		let mut events = LinkEvents { client: self.client.clone(), filter, metadata: None, _marker: PhantomData };
+		let stream = blocks
+			.flat_map(move |(hash, number)| futures::stream::iter(events.at(hash, number)))
+			.map(|notification| Ok::<_, ()>(notification))
+			.compat();
+
+		self.manager.add(subscriber, |sink| {
+			let stream = stream.map(|notification| Ok(notification));
+			sink.sink_map_err(|e| log::warn!("Error sending link notifications: {:?}", e))
+				.send_all(stream)
+				.map(|_| ())
+		});
+	}
+
+	fn unsubscribe_links(&self, _metadata: Option<Self::Metadata>, id: SubscriptionId) -> jsonrpc_core::Result<bool> {
+		Ok(self.manager.cancel(id))
+	}
+}
+
+/// Finds the link events of a subscription in each block.
+struct LinkEvents<C, B> {
+	client: Arc<C>,
+	filter: LinkFilter,
+	/// The metadata of the runtime the last block was executed with, by code hash.
+	metadata: Option<(Option<Hash>, Metadata)>,
+	_marker: PhantomData<B>,
+}
+
+impl<C, B> LinkEvents<C, B> where
+	C: StorageProvider<Block, B> + ProvideRuntimeApi<Block>,
+	C::Api: MetadataApi<Block> + LinkIndexApi<Block, AccountId>,
+	B: Backend<Block>,
+{
+	fn at(&mut self, hash: Hash, number: u32) -> Vec<LinkNotification> {
+		self.notifications(hash, number).unwrap_or_else(|e| {
+			log::warn!("Link subscription skipped block #{}: {}", number, e);
+			Vec::new()
+		})
+	}
+
+	fn storage(&self, at: &BlockId<Block>, key: StorageKey) -> Result<Option<Vec<u8>>, String> {
+		self.client.storage(at, &key)
+			.map(|value| value.map(|value| value.0))
+			.map_err(|e| format!("{:?}", e))
+	}
+
+	fn notifications(&mut self, hash: Hash, number: u32) -> Result<Vec<LinkNotification>, String> {
+		let at = BlockId::Hash(hash);
+		let code_hash = self.client.storage_hash(&at, &StorageKey(well_known_keys::CODE.to_vec()))
+			.map_err(|e| format!("{:?}", e))?;
+		if self.metadata.as_ref().map(|(hash, _)| *hash) != Some(code_hash) {
+			let metadata = self.client.runtime_api().metadata(&at).map_err(|e| format!("{:?}", e))?;
+			self.metadata = Some((code_hash, Metadata::from_encoded(&metadata)?));
+		}
+		let metadata = &self.metadata.as_ref().expect("set above; qed").1;
+
+		let events = self.storage(&at, StorageKey([twox_128(b"System"), twox_128(b"Events")].concat()))?
+			.unwrap_or_else(|| vec![0]);
+		// Events after one that doesn't decode are lost, but the linker events before it still count.
+		let events = metadata.decode_events_lossy(&events)?;
+		if let Some(undecoded) = &events.undecoded {
+			log::warn!(
+				"Link subscription: events {} to {} of #{} not decoded: {}",
+				undecoded.index, undecoded.index + undecoded.count - 1, number, undecoded.error,
+			);
+		}
+
+		let mut notifications = Vec::new();
+		for event in events.decoded.iter().filter(|event| event["pallet"] == "AccountLinkerModule") {
+			let name = event["event"].as_str().unwrap_or_default();
+			let types = metadata.module("AccountLinkerModule")?.event.iter().flatten()
+				.find(|meta| meta.name == name)
+				.map(|meta| meta.arguments.clone())
+				.unwrap_or_default();
+			let account = types.iter()
+				.zip(event["args"].as_array().into_iter().flatten())
+				.find(|(ty, _)| scale::canonical(ty) == "AccountId")
+				.and_then(|(_, arg)| arg.as_str())
+				.and_then(|account| AccountId::from_ss58check(account).ok());
+			let account = match account {
+				Some(account) => account,
+				None => continue,
+			};
+
+			let runtime_api = self.client.runtime_api();
+			let ethereum_links = runtime_api.ethereum_links(&at, account.clone())
+				.map(ethereum_links)
+				.map_err(|e| format!("{:?}", e))?;
+			let bitcoin_links = runtime_api.bitcoin_links(&at, account.clone())
+				.map(bitcoin_links)
+				.map_err(|e| format!("{:?}", e))?;
+			if !self.filter.matches(&account, &event["args"], &ethereum_links, &bitcoin_links) {
+				continue;
+			}
+
+			notifications.push(LinkNotification {
+				block_hash: hash,
+				block_number: number,
+				event: name.to_string(),
+				args: event["args"].clone(),
+				account,
+				ethereum_links,
+				bitcoin_links,
+			});
+		}
+
+		Ok(notifications)
+	}
+}
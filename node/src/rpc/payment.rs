@@ -0,0 +1,79 @@
+//! `payment_queryInfo` for light clients.
+//!
+//! The upstream handler calls the runtime API locally, which a light client
+//! can't do, so the call is forwarded to full peers instead.
+
+use std::sync::Arc;
+
+use codec::{Decode, Encode};
+use futures::{FutureExt, TryFutureExt};
+use jsonrpc_derive::rpc;
+use litentry_runtime::{opaque::Block, Balance, Hash};
+use pallet_transaction_payment_rpc_runtime_api::RuntimeDispatchInfo;
+use sc_client_api::light::{Fetcher, RemoteBlockchain};
+use sp_blockchain::HeaderBackend;
+use sp_core::Bytes;
+use sp_runtime::traits::Block as BlockT;
+
+use super::{client_err, decode_err, light::remote_call, FutureResult};
+
+pub use self::gen_client::Client as LightTransactionPaymentClient;
+
+/// Transaction payment queries served by a light client.
+#[rpc]
+pub trait LightTransactionPaymentApi<BlockHash, ResponseType> {
+	/// Weight, class and partial fee of an encoded extrinsic.
+	#[rpc(name = "payment_queryInfo")]
+	fn query_info(&self, encoded_xt: Bytes, at: Option<BlockHash>) -> FutureResult<ResponseType>;
+}
+
+/// Light client implementation of [`LightTransactionPaymentApi`].
+pub struct LightTransactionPayment<C, F> {
+	client: Arc<C>,
+	remote_blockchain: Arc<dyn RemoteBlockchain<Block>>,
+	fetcher: Arc<F>,
+}
+
+impl<C, F> LightTransactionPayment<C, F> {
+	/// Create a new `LightTransactionPayment`.
+	pub fn new(
+		client: Arc<C>,
+		remote_blockchain: Arc<dyn RemoteBlockchain<Block>>,
+		fetcher: Arc<F>,
+	) -> Self {
+		LightTransactionPayment { client, remote_blockchain, fetcher }
+	}
+}
+
+impl<C, F> LightTransactionPaymentApi<Hash, RuntimeDispatchInfo<Balance>>
+	for LightTransactionPayment<C, F>
+where
+	C: HeaderBackend<Block> + Send + Sync + 'static,
+	F: Fetcher<Block> + 'static,
+{
+	fn query_info(
+		&self,
+		encoded_xt: Bytes,
+		at: Option<Hash>,
+	) -> FutureResult<RuntimeDispatchInfo<Balance>> {
+		let encoded_len = encoded_xt.len() as u32;
+		let uxt = match <Block as BlockT>::Extrinsic::decode(&mut &*encoded_xt) {
+			Ok(uxt) => uxt,
+			Err(e) => return Box::new(jsonrpc_core::futures::future::err(decode_err(e))),
+		};
+
+		let future = remote_call(
+			self.client.info().best_hash,
+			&*self.remote_blockchain,
+			self.fetcher.clone(),
+			at,
+			"TransactionPaymentApi_query_info",
+			(uxt, encoded_len).encode(),
+		).map(|result| result
+			.map_err(client_err)
+			.and_then(|info| RuntimeDispatchInfo::<Balance>::decode(&mut &info[..]).map_err(decode_err))
+		);
+
+		Box::new(future.boxed().compat())
+	}
+}
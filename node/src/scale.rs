@@ -0,0 +1,1165 @@
+//! SCALE encoding and decoding of values described by the type strings of the
+//! runtime metadata.
+//!
+//! Metadata V12 only names the types of call arguments, event fields and storage
+//! values, e.g. `Compact<T::Balance>` or `<T::Lookup as StaticLookup>::Source`.
+//! Those names are resolved against the primitives below and the runtime's own
+//! definitions in [`TYPES`]. Values are JSON; anything this module can't express
+//! can be passed pre-encoded as `"scale:0x.."`.
+
+use codec::{Compact, Decode, Encode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sp_core::{crypto::{AccountId32, Ss58Codec}, hashing::twox_128};
+use sp_runtime::generic::Era;
+use frame_metadata::{RuntimeMetadata, RuntimeMetadataPrefixed};
+
+/// Definition of a type the runtime metadata refers to by name.
+pub enum TypeDef {
+	/// Another name for a type.
+	Alias(&'static str),
+	/// Fields in encoding order.
+	Struct(&'static [(&'static str, &'static str)]),
+	/// Variants in index order, with their payload type if any.
+	Enum(&'static [(&'static str, Option<&'static str>)]),
+}
+
+/// Types of this runtime and of the FRAME pallets it uses that appear in call
+/// arguments, events and storage, beyond what is built into the codec.
+pub const TYPES: &[(&str, TypeDef)] = &[
+	("Address", TypeDef::Alias("AccountId")),
+	("LookupSource", TypeDef::Alias("AccountId")),
+	("Balance", TypeDef::Alias("u128")),
+	("BalanceOf", TypeDef::Alias("Balance")),
+	("BlockNumber", TypeDef::Alias("u32")),
+	("Index", TypeDef::Alias("u32")),
+	("Moment", TypeDef::Alias("u64")),
+	("Weight", TypeDef::Alias("u64")),
+	("Perbill", TypeDef::Alias("u32")),
+	("Permill", TypeDef::Alias("u32")),
+	("Percent", TypeDef::Alias("u8")),
+	("EthAddress", TypeDef::Alias("[u8; 20]")),
+	("Key", TypeDef::Alias("Bytes")),
+	("StorageKey", TypeDef::Alias("Bytes")),
+	("StorageData", TypeDef::Alias("Bytes")),
+	("KeyValue", TypeDef::Alias("(StorageKey, StorageData)")),
+	("ProposalIndex", TypeDef::Alias("u32")),
+	("ReferendumIndex", TypeDef::Alias("u32")),
+	("PropIndex", TypeDef::Alias("u32")),
+	("MemberCount", TypeDef::Alias("u32")),
+	("RegistrarIndex", TypeDef::Alias("u32")),
+	("Priority", TypeDef::Alias("u8")),
+	("Period", TypeDef::Alias("(BlockNumber, u32)")),
+	("TaskAddress", TypeDef::Alias("(BlockNumber, u32)")),
+	("Vote", TypeDef::Alias("u8")),
+	("Timepoint", TypeDef::Struct(&[("height", "BlockNumber"), ("index", "u32")])),
+	("ProxyType", TypeDef::Enum(&[("Any", None), ("NonTransfer", None), ("Governance", None)])),
+	("Conviction", TypeDef::Enum(&[
+		("None", None),
+		("Locked1x", None),
+		("Locked2x", None),
+		("Locked3x", None),
+		("Locked4x", None),
+		("Locked5x", None),
+		("Locked6x", None),
+	])),
+	("AccountVote", TypeDef::Enum(&[
+		("Standard", Some("AccountVoteStandard")),
+		("Split", Some("AccountVoteSplit")),
+	])),
+	("AccountVoteStandard", TypeDef::Struct(&[("vote", "Vote"), ("balance", "Balance")])),
+	("AccountVoteSplit", TypeDef::Struct(&[("aye", "Balance"), ("nay", "Balance")])),
+	("Null", TypeDef::Alias("()")),
+	("CallHash", TypeDef::Alias("Hash")),
+	("AuthorityId", TypeDef::Alias("[u8; 32]")),
+	("AuthorityWeight", TypeDef::Alias("u64")),
+	("AuthorityList", TypeDef::Alias("Vec<(AuthorityId, AuthorityWeight)>")),
+	("MultiSignature", TypeDef::Enum(&[
+		("Ed25519", Some("H512")),
+		("Sr25519", Some("H512")),
+		("Ecdsa", Some("[u8; 65]")),
+	])),
+	("Phase", TypeDef::Enum(&[
+		("ApplyExtrinsic", Some("u32")),
+		("Finalization", None),
+		("Initialization", None),
+	])),
+	("DispatchClass", TypeDef::Enum(&[("Normal", None), ("Operational", None), ("Mandatory", None)])),
+	("Pays", TypeDef::Enum(&[("Yes", None), ("No", None)])),
+	("DispatchInfo", TypeDef::Struct(&[("weight", "Weight"), ("class", "DispatchClass"), ("paysFee", "Pays")])),
+	("DispatchError", TypeDef::Enum(&[
+		("Other", None),
+		("CannotLookup", None),
+		("BadOrigin", None),
+		("Module", Some("DispatchErrorModule")),
+		("ConsumerRemaining", None),
+		("NoProviders", None),
+	])),
+	("DispatchErrorModule", TypeDef::Struct(&[("index", "u8"), ("error", "u8")])),
+	("DispatchResult", TypeDef::Enum(&[("Ok", None), ("Err", Some("DispatchError"))])),
+	("BalanceStatus", TypeDef::Enum(&[("Free", None), ("Reserved", None)])),
+	("Status", TypeDef::Alias("BalanceStatus")),
+	("VoteThreshold", TypeDef::Enum(&[
+		("SuperMajorityApprove", None),
+		("SuperMajorityAgainst", None),
+		("SimpleMajority", None),
+	])),
+];
+
+/// Argument of a dispatchable, as named in the metadata.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Argument {
+	pub name: String,
+	pub ty: String,
+}
+
+/// A dispatchable of a pallet.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Function {
+	pub name: String,
+	pub arguments: Vec<Argument>,
+}
+
+/// An event of a pallet.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventMeta {
+	pub name: String,
+	pub arguments: Vec<String>,
+}
+
+/// An error of a pallet.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ErrorMeta {
+	pub name: String,
+	#[serde(default)]
+	pub documentation: Vec<String>,
+}
+
+/// How the keys of a storage map are hashed.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum StorageHasher {
+	Blake2_128,
+	Blake2_256,
+	Blake2_128Concat,
+	Twox128,
+	Twox256,
+	Twox64Concat,
+	Identity,
+}
+
+/// Layout of a storage item.
+#[derive(Debug, Clone, Deserialize)]
+pub enum StorageEntryType {
+	Plain(String),
+	Map {
+		hasher: StorageHasher,
+		key: String,
+		value: String,
+	},
+	DoubleMap {
+		hasher: StorageHasher,
+		key1: String,
+		key2: String,
+		value: String,
+		key2_hasher: StorageHasher,
+	},
+}
+
+impl StorageEntryType {
+	/// Type of the stored values.
+	pub fn value(&self) -> &str {
+		match self {
+			StorageEntryType::Plain(value) => value,
+			StorageEntryType::Map { value, .. } => value,
+			StorageEntryType::DoubleMap { value, .. } => value,
+		}
+	}
+}
+
+/// A storage item of a pallet.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StorageEntry {
+	pub name: String,
+	pub ty: StorageEntryType,
+}
+
+/// The storage items of a pallet.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StorageMeta {
+	pub prefix: String,
+	pub entries: Vec<StorageEntry>,
+}
+
+/// A pallet of the runtime.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Module {
+	pub name: String,
+	pub index: u8,
+	#[serde(default)]
+	pub storage: Option<StorageMeta>,
+	#[serde(default)]
+	pub calls: Option<Vec<Function>>,
+	#[serde(default)]
+	pub event: Option<Vec<EventMeta>>,
+	#[serde(default)]
+	pub errors: Vec<ErrorMeta>,
+}
+
+/// Events decoded by [`Metadata::decode_events_lossy`].
+pub struct Events {
+	/// The events before the first one that failed to decode.
+	pub decoded: Vec<Value>,
+	/// That event and the ones after it.
+	pub undecoded: Option<UndecodedEvents>,
+}
+
+/// Events that couldn't be decoded, starting with the one that failed.
+pub struct UndecodedEvents {
+	/// Index of the failed event in the block.
+	pub index: usize,
+	/// Number of events in `raw`.
+	pub count: usize,
+	/// Phase, pallet and name of the failed event, as far as they decode.
+	pub header: Value,
+	/// The encoded events.
+	pub raw: Vec<u8>,
+	/// Why the failed event couldn't be decoded.
+	pub error: String,
+}
+
+/// The modules of the runtime metadata, with every lazily encoded part resolved.
+pub struct Metadata {
+	pub modules: Vec<Module>,
+}
+
+impl Metadata {
+	/// Metadata of the runtime compiled into the node.
+	pub fn native() -> Result<Self, String> {
+		Self::from_prefixed(litentry_runtime::Runtime::metadata())
+	}
+
+	/// Metadata from a `RuntimeMetadataPrefixed`, as returned by `state_getMetadata`.
+	pub fn from_prefixed(metadata: RuntimeMetadataPrefixed) -> Result<Self, String> {
+		let modules = match metadata.1 {
+			RuntimeMetadata::V12(metadata) => serde_json::to_value(&metadata.modules),
+			_ => return Err("Only metadata V12 is supported".into()),
+		};
+		let modules = modules
+			.and_then(serde_json::from_value)
+			.map_err(|e| format!("Unexpected metadata layout: {}", e))?;
+
+		Ok(Metadata { modules })
+	}
+
+	/// Metadata from its SCALE encoding, as returned by the `Metadata` runtime API.
+	pub fn from_encoded(bytes: &[u8]) -> Result<Self, String> {
+		let metadata = RuntimeMetadataPrefixed::decode(&mut &bytes[..])
+			.map_err(|e| format!("Invalid metadata: {}", e))?;
+		Self::from_prefixed(metadata)
+	}
+
+	/// The pallet called `name`, ignoring case.
+	pub fn module(&self, name: &str) -> Result<&Module, String> {
+		self.modules.iter()
+			.find(|module| module.name.eq_ignore_ascii_case(name))
+			.ok_or_else(|| format!("No pallet `{}` in the runtime", name))
+	}
+
+	/// Encode the call `pallet::call(args)`. Call names may be snake or camel case.
+	pub fn encode_call(&self, pallet: &str, call: &str, args: &[Value]) -> Result<Vec<u8>, String> {
+		let module = self.module(pallet)?;
+		let call = to_snake_case(call);
+		let (call_index, function) = module.calls.iter().flatten()
+			.enumerate()
+			.find(|(_, function)| function.name == call)
+			.ok_or_else(|| format!("No call `{}` in pallet `{}`", call, module.name))?;
+
+		if args.len() != function.arguments.len() {
+			return Err(format!(
+				"`{}::{}` takes {} arguments ({}), got {}",
+				module.name,
+				function.name,
+				function.arguments.len(),
+				function.arguments.iter()
+					.map(|arg| format!("{}: {}", arg.name, arg.ty))
+					.collect::<Vec<_>>()
+					.join(", "),
+				args.len(),
+			));
+		}
+
+		let mut encoded = vec![module.index, call_index as u8];
+		for (arg, value) in function.arguments.iter().zip(args) {
+			let arg_encoded = encode(&arg.ty, value)
+				.map_err(|e| format!("Argument `{}`: {}", arg.name, e))?;
+			encoded.extend(arg_encoded);
+		}
+
+		Ok(encoded)
+	}
+
+	/// The pallet at `index` in `construct_runtime!`.
+	pub fn module_at(&self, index: u8) -> Result<&Module, String> {
+		self.modules.iter()
+			.find(|module| module.index == index)
+			.ok_or_else(|| format!("No pallet with index {}", index))
+	}
+
+	/// Decode a complete value of type `ty`.
+	pub fn decode(&self, ty: &str, bytes: &[u8]) -> Result<Value, String> {
+		let input = &mut &bytes[..];
+		let value = self.decode_from(&parse(ty)?, input)?;
+		ensure_consumed(input)?;
+		Ok(value)
+	}
+
+	/// Decode a call: pallet, call name and named arguments.
+	pub fn decode_call(&self, input: &mut &[u8]) -> Result<Value, String> {
+		let module = self.module_at(decode_codec::<u8>(input)?)?;
+		let call_index = decode_codec::<u8>(input)?;
+		let function = module.calls.iter().flatten()
+			.nth(call_index as usize)
+			.ok_or_else(|| format!("No call with index {} in `{}`", call_index, module.name))?;
+
+		let mut args = serde_json::Map::new();
+		for arg in &function.arguments {
+			let value = self.decode_from(&parse(&arg.ty)?, input)
+				.map_err(|e| format!("`{}::{}` argument `{}`: {}", module.name, function.name, arg.name, e))?;
+			args.insert(arg.name.clone(), value);
+		}
+
+		Ok(json!({ "pallet": module.name, "call": function.name, "args": args }))
+	}
+
+	/// Decode an encoded `UncheckedExtrinsic`, with its length prefix.
+	pub fn decode_extrinsic(&self, bytes: &[u8]) -> Result<Value, String> {
+		let input = &mut &bytes[..];
+		let len = decode_codec::<Compact<u32>>(input)?.0 as usize;
+		if len != input.len() {
+			return Err(format!("Length prefix says {} bytes, got {}", len, input.len()));
+		}
+
+		let version = decode_codec::<u8>(input)?;
+		if version & 0b0111_1111 != 4 {
+			return Err(format!("Unsupported extrinsic version {}", version & 0b0111_1111));
+		}
+
+		let mut extrinsic = serde_json::Map::new();
+		if version & 0b1000_0000 != 0 {
+			extrinsic.insert("signer".into(), self.decode_from(&parse("Address")?, input)?);
+			extrinsic.insert("signature".into(), self.decode_from(&parse("MultiSignature")?, input)?);
+			let era = decode_codec::<Era>(input)?;
+			extrinsic.insert("era".into(), serde_json::to_value(era).map_err(|e| e.to_string())?);
+			extrinsic.insert("nonce".into(), self.decode_from(&parse("Compact<Index>")?, input)?);
+			extrinsic.insert("tip".into(), self.decode_from(&parse("Compact<Balance>")?, input)?);
+		}
+		extrinsic.insert("call".into(), self.decode_call(input)?);
+		ensure_consumed(input)?;
+
+		Ok(Value::Object(extrinsic))
+	}
+
+	/// Decode one `EventRecord` from `input`.
+	pub fn decode_event_record(&self, input: &mut &[u8]) -> Result<Value, String> {
+		let phase = self.decode_from(&parse("Phase")?, input)?;
+		let module = self.module_at(decode_codec::<u8>(input)?)?;
+		let event_index = decode_codec::<u8>(input)?;
+		let event = module.event.iter().flatten()
+			.nth(event_index as usize)
+			.ok_or_else(|| format!("No event with index {} in `{}`", event_index, module.name))?;
+
+		let args = event.arguments.iter()
+			.map(|ty| {
+				parse(ty)
+					.and_then(|ty| self.decode_from(&ty, input))
+					.map_err(|e| format!("`{}::{}`: {}", module.name, event.name, e))
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+		let topics = self.decode_from(&parse("Vec<Hash>")?, input)?;
+
+		Ok(json!({
+			"phase": phase,
+			"pallet": module.name,
+			"event": event.name,
+			"args": args,
+			"topics": topics,
+		}))
+	}
+
+	/// Decode the `Vec<EventRecord>` stored in `System::Events`.
+	pub fn decode_events(&self, bytes: &[u8]) -> Result<Value, String> {
+		let input = &mut &bytes[..];
+		let count = decode_codec::<Compact<u32>>(input)?.0;
+		let events = (0..count)
+			.map(|_| self.decode_event_record(input))
+			.collect::<Result<Vec<_>, _>>()?;
+		ensure_consumed(input)?;
+
+		Ok(Value::Array(events))
+	}
+
+	/// Decode the `Vec<EventRecord>` stored in `System::Events` as far as possible.
+	///
+	/// Events don't carry their length, so nothing after an event that fails to
+	/// decode can be found; it and the rest are returned still encoded.
+	pub fn decode_events_lossy(&self, bytes: &[u8]) -> Result<Events, String> {
+		let input = &mut &bytes[..];
+		let count = decode_codec::<Compact<u32>>(input)?.0;
+
+		let mut decoded = Vec::new();
+		for index in 0..count as usize {
+			let start = *input;
+			match self.decode_event_record(input) {
+				Ok(event) => decoded.push(event),
+				Err(error) => return Ok(Events {
+					decoded,
+					undecoded: Some(UndecodedEvents {
+						index,
+						count: count as usize - index,
+						header: self.decode_event_header(&mut &start[..]),
+						raw: start.to_vec(),
+						error,
+					}),
+				}),
+			}
+		}
+		ensure_consumed(input)?;
+
+		Ok(Events { decoded, undecoded: None })
+	}
+
+	/// Phase, pallet and name of an event record, as far as they decode.
+	fn decode_event_header(&self, input: &mut &[u8]) -> Value {
+		let mut header = serde_json::Map::new();
+		if let Ok(phase) = self.decode_from(&Ty::Named("Phase".into()), input) {
+			header.insert("phase".into(), phase);
+			let module = decode_codec::<u8>(input).and_then(|index| self.module_at(index));
+			if let Ok(module) = module {
+				header.insert("pallet".into(), module.name.clone().into());
+				let event = decode_codec::<u8>(input).ok()
+					.and_then(|index| module.event.iter().flatten().nth(index as usize));
+				if let Some(event) = event {
+					header.insert("event".into(), event.name.clone().into());
+				}
+			}
+		}
+		Value::Object(header)
+	}
+
+	/// The storage item a key belongs to, and the map keys it contains.
+	pub fn decode_storage_key(&self, key: &[u8]) -> Result<(&StorageEntry, Value), String> {
+		if key.len() < 32 {
+			return Err("Storage keys are at least 32 bytes".into());
+		}
+
+		let (module, entry) = self.modules.iter()
+			.filter_map(|module| module.storage.as_ref().map(|storage| (module, storage)))
+			.flat_map(|(module, storage)| {
+				let prefix = twox_128(storage.prefix.as_bytes());
+				storage.entries.iter()
+					.filter(move |entry| {
+						key[..16] == prefix[..] && key[16..32] == twox_128(entry.name.as_bytes())[..]
+					})
+					.map(move |entry| (module, entry))
+			})
+			.next()
+			.ok_or("No storage item of the runtime has this prefix")?;
+
+		let input = &mut &key[32..];
+		let keys = match &entry.ty {
+			StorageEntryType::Plain(_) => Vec::new(),
+			StorageEntryType::Map { hasher, key, .. } => vec![self.decode_hashed(*hasher, key, input)?],
+			StorageEntryType::DoubleMap { hasher, key1, key2, key2_hasher, .. } => vec![
+				self.decode_hashed(*hasher, key1, input)?,
+				self.decode_hashed(*key2_hasher, key2, input)?,
+			],
+		};
+		ensure_consumed(input)?;
+
+		Ok((entry, json!({ "pallet": module.name, "storage": entry.name, "keys": keys })))
+	}
+
+	fn decode_hashed(&self, hasher: StorageHasher, ty: &str, input: &mut &[u8]) -> Result<Value, String> {
+		let (hash_len, transparent) = match hasher {
+			StorageHasher::Blake2_128Concat => (16, true),
+			StorageHasher::Twox64Concat => (8, true),
+			StorageHasher::Identity => (0, true),
+			StorageHasher::Blake2_128 | StorageHasher::Twox128 => (16, false),
+			StorageHasher::Blake2_256 | StorageHasher::Twox256 => (32, false),
+		};
+		if input.len() < hash_len {
+			return Err("Storage key ends in the middle of a hash".into());
+		}
+
+		let hash = &input[..hash_len];
+		*input = &input[hash_len..];
+		if transparent {
+			self.decode_from(&parse(ty)?, input)
+		} else {
+			Ok(json!({ "hash": format!("0x{}", hex::encode(hash)) }))
+		}
+	}
+
+	fn decode_from(&self, ty: &Ty, input: &mut &[u8]) -> Result<Value, String> {
+		Ok(match ty {
+			Ty::Bool => Value::Bool(decode_codec(input)?),
+			Ty::Uint(8) => decode_codec::<u8>(input)?.into(),
+			Ty::Uint(16) => decode_codec::<u16>(input)?.into(),
+			Ty::Uint(32) => decode_codec::<u32>(input)?.into(),
+			Ty::Uint(64) => decode_codec::<u64>(input)?.into(),
+			Ty::Uint(_) => decode_codec::<u128>(input)?.to_string().into(),
+			Ty::Int(8) => decode_codec::<i8>(input)?.into(),
+			Ty::Int(16) => decode_codec::<i16>(input)?.into(),
+			Ty::Int(32) => decode_codec::<i32>(input)?.into(),
+			Ty::Int(64) => decode_codec::<i64>(input)?.into(),
+			Ty::Int(_) => decode_codec::<i128>(input)?.to_string().into(),
+			Ty::Compact(_) => {
+				let n = decode_codec::<Compact<u128>>(input)?.0;
+				if n <= u64::MAX as u128 { (n as u64).into() } else { n.to_string().into() }
+			},
+			Ty::Bytes => to_hex(&decode_codec::<Vec<u8>>(input)?),
+			Ty::Text => Value::String(decode_codec(input)?),
+			Ty::Vec(inner) => {
+				let len = decode_codec::<Compact<u32>>(input)?.0;
+				(0..len)
+					.map(|_| self.decode_from(inner, input))
+					.collect::<Result<Vec<_>, _>>()?
+					.into()
+			},
+			Ty::Option(inner) => match decode_codec::<u8>(input)? {
+				0 => Value::Null,
+				1 => self.decode_from(inner, input)?,
+				b => return Err(format!("Invalid Option tag {}", b)),
+			},
+			Ty::Array(inner, len) if **inner == Ty::Uint(8) => to_hex(&take(input, *len)?),
+			Ty::Array(inner, len) => (0..*len)
+				.map(|_| self.decode_from(inner, input))
+				.collect::<Result<Vec<_>, _>>()?
+				.into(),
+			Ty::Tuple(types) if types.is_empty() => Value::Null,
+			Ty::Tuple(types) => types.iter()
+				.map(|ty| self.decode_from(ty, input))
+				.collect::<Result<Vec<_>, _>>()?
+				.into(),
+			Ty::AccountId => decode_codec::<AccountId32>(input)?.to_ss58check().into(),
+			Ty::Hash(len) => to_hex(&take(input, *len)?),
+			Ty::Call => self.decode_call(input)?,
+			Ty::Named(name) if name == "DispatchErrorModule" => {
+				let index = decode_codec::<u8>(input)?;
+				let error = decode_codec::<u8>(input)?;
+				let module = self.module_at(index)?;
+				let meta = module.errors.get(error as usize);
+
+				json!({
+					"index": index,
+					"error": error,
+					"pallet": module.name,
+					"name": meta.map(|meta| meta.name.clone()),
+					"documentation": meta.map(|meta| meta.documentation.join(" ").trim().to_string()),
+				})
+			},
+			Ty::Named(name) => match type_def(name) {
+				Some(TypeDef::Alias(alias)) => self.decode_from(&parse(alias)?, input)?,
+				Some(TypeDef::Struct(fields)) => {
+					let mut object = serde_json::Map::new();
+					for (field, ty) in fields.iter() {
+						object.insert(field.to_string(), self.decode_from(&parse(ty)?, input)?);
+					}
+					Value::Object(object)
+				},
+				Some(TypeDef::Enum(variants)) => {
+					let index = decode_codec::<u8>(input)?;
+					let (variant, payload_ty) = variants.get(index as usize)
+						.ok_or_else(|| format!("Invalid `{}` variant {}", name, index))?;
+
+					match payload_ty {
+						Some(ty) => {
+							let mut object = serde_json::Map::new();
+							object.insert(variant.to_string(), self.decode_from(&parse(ty)?, input)?);
+							Value::Object(object)
+						},
+						None => Value::String(variant.to_string()),
+					}
+				},
+				None => return Err(format!("Unsupported type `{}`", name)),
+			},
+			Ty::Generic(name, _) => return Err(format!("Unsupported generic type `{}`", name)),
+		})
+	}
+}
+
+fn decode_codec<T: Decode>(input: &mut &[u8]) -> Result<T, String> {
+	T::decode(input).map_err(|e| format!("{} while decoding {}", e, std::any::type_name::<T>()))
+}
+
+fn take(input: &mut &[u8], len: usize) -> Result<Vec<u8>, String> {
+	if input.len() < len {
+		return Err(format!("Expected {} more bytes, got {}", len, input.len()));
+	}
+	let (taken, rest) = input.split_at(len);
+	*input = rest;
+	Ok(taken.to_vec())
+}
+
+fn ensure_consumed(input: &[u8]) -> Result<(), String> {
+	match input.len() {
+		0 => Ok(()),
+		left => Err(format!("{} bytes left over after decoding", left)),
+	}
+}
+
+fn to_hex(bytes: &[u8]) -> Value {
+	Value::String(format!("0x{}", hex::encode(bytes)))
+}
+
+fn to_snake_case(name: &str) -> String {
+	let mut snake = String::with_capacity(name.len());
+	for c in name.chars() {
+		if c.is_ascii_uppercase() {
+			if !snake.is_empty() {
+				snake.push('_');
+			}
+			snake.push(c.to_ascii_lowercase());
+		} else {
+			snake.push(c);
+		}
+	}
+	snake
+}
+
+/// A parsed type string.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ty {
+	Bool,
+	Uint(usize),
+	Int(usize),
+	Compact(Box<Ty>),
+	Vec(Box<Ty>),
+	Option(Box<Ty>),
+	Array(Box<Ty>, usize),
+	Tuple(Vec<Ty>),
+	Bytes,
+	Text,
+	AccountId,
+	/// A fixed size hash of this many bytes.
+	Hash(usize),
+	/// An encoded runtime call.
+	Call,
+	Named(String),
+	/// A type with parameters that has no definition in [`TYPES`].
+	Generic(String, Vec<Ty>),
+}
+
+/// Strip the generic parameters the metadata leaves in type names.
+fn normalize(ty: &str) -> String {
+	let mut ty = ty.replace("<T::Lookup as StaticLookup>::Source", "LookupSource");
+
+	// `<T as Config>::X`, `<T as frame_system::Config>::X`, `<T as Config<I>>::X`
+	while let Some(start) = ty.find("<T as ") {
+		let mut depth = 0;
+		let end = ty[start..].char_indices()
+			.find(|(_, c)| {
+				match c {
+					'<' => depth += 1,
+					'>' => depth -= 1,
+					_ => (),
+				}
+				depth == 0
+			})
+			.map(|(i, _)| start + i);
+
+		match end {
+			Some(end) if ty[end + 1..].starts_with("::") => ty.replace_range(start..end + 3, ""),
+			_ => break,
+		}
+	}
+
+	let mut ty = ty.replace("T::", "").replace("<T>", "").replace("<T, I>", "");
+
+	// Module paths, e.g. `schedule::Period`
+	while let Some(end) = ty.find("::") {
+		let start = ty[..end].rfind(|c: char| !(c.is_alphanumeric() || c == '_')).map_or(0, |i| i + 1);
+		ty.replace_range(start..end + 2, "");
+	}
+
+	ty
+}
+
+/// Split `s` at `separator`s outside of brackets.
+fn split_top(s: &str, separator: char) -> Vec<&str> {
+	let mut parts = Vec::new();
+	let mut depth = 0i32;
+	let mut start = 0;
+	for (i, c) in s.char_indices() {
+		match c {
+			'<' | '(' | '[' => depth += 1,
+			'>' | ')' | ']' => depth -= 1,
+			c if c == separator && depth == 0 => {
+				parts.push(s[start..i].trim());
+				start = i + c.len_utf8();
+			},
+			_ => (),
+		}
+	}
+	parts.push(s[start..].trim());
+	parts.retain(|part| !part.is_empty());
+	parts
+}
+
+/// Parse a type string from the metadata or [`TYPES`].
+pub fn parse(ty: &str) -> Result<Ty, String> {
+	let ty = normalize(ty);
+	let ty = ty.trim();
+
+	if ty.starts_with('(') && ty.ends_with(')') {
+		return split_top(&ty[1..ty.len() - 1], ',').into_iter()
+			.map(parse)
+			.collect::<Result<_, _>>()
+			.map(Ty::Tuple);
+	}
+
+	if ty.starts_with('[') && ty.ends_with(']') {
+		let inner = &ty[1..ty.len() - 1];
+		let separator = inner.rfind(';').ok_or_else(|| format!("Invalid array type `{}`", ty))?;
+		let len = inner[separator + 1..].trim().parse()
+			.map_err(|_| format!("Invalid array length in `{}`", ty))?;
+		return Ok(Ty::Array(Box::new(parse(&inner[..separator])?), len));
+	}
+
+	if let (Some(open), true) = (ty.find('<'), ty.ends_with('>')) {
+		let params = &ty[open + 1..ty.len() - 1];
+		return match &ty[..open] {
+			"Compact" => Ok(Ty::Compact(Box::new(parse(params)?))),
+			"Vec" | "BTreeSet" | "VecDeque" => Ok(match parse(params)? {
+				Ty::Uint(8) => Ty::Bytes,
+				inner => Ty::Vec(Box::new(inner)),
+			}),
+			"Option" => Ok(Ty::Option(Box::new(parse(params)?))),
+			"Box" => parse(params),
+			// Runtime types are defined for the parameters this runtime uses.
+			name if type_def(name).is_some() => Ok(Ty::Named(name.to_string())),
+			name => split_top(params, ',').into_iter()
+				.map(parse)
+				.collect::<Result<_, _>>()
+				.map(|params| Ty::Generic(name.to_string(), params)),
+		};
+	}
+
+	Ok(match ty {
+		"bool" => Ty::Bool,
+		"u8" => Ty::Uint(8),
+		"u16" => Ty::Uint(16),
+		"u32" => Ty::Uint(32),
+		"u64" => Ty::Uint(64),
+		"u128" => Ty::Uint(128),
+		"i8" => Ty::Int(8),
+		"i16" => Ty::Int(16),
+		"i32" => Ty::Int(32),
+		"i64" => Ty::Int(64),
+		"i128" => Ty::Int(128),
+		"Bytes" => Ty::Bytes,
+		"String" | "Text" => Ty::Text,
+		"AccountId" => Ty::AccountId,
+		"H160" => Ty::Hash(20),
+		"H256" | "Hash" => Ty::Hash(32),
+		"H512" => Ty::Hash(64),
+		"Call" | "Proposal" => Ty::Call,
+		name => Ty::Named(name.to_string()),
+	})
+}
+
+/// The definition of the runtime type `name`.
+pub fn type_def(name: &str) -> Option<&'static TypeDef> {
+	TYPES.iter().find(|(n, _)| *n == name).map(|(_, def)| def)
+}
+
+/// `ty` with the runtime's aliases resolved, so that types which encode the
+/// same compare equal, e.g. `T::Balance` and `BalanceOf<T>`.
+pub fn canonical(ty: &str) -> String {
+	match parse(ty) {
+		Ok(ty) => canonical_ty(&ty),
+		Err(_) => normalize(ty),
+	}
+}
+
+/// [`canonical`] for a parsed type.
+pub fn canonical_ty(ty: &Ty) -> String {
+	match ty {
+		Ty::Bool => "bool".into(),
+		Ty::Uint(bits) => format!("u{}", bits),
+		Ty::Int(bits) => format!("i{}", bits),
+		Ty::Compact(inner) => format!("Compact<{}>", canonical_ty(inner)),
+		Ty::Vec(inner) => format!("Vec<{}>", canonical_ty(inner)),
+		Ty::Option(inner) => format!("Option<{}>", canonical_ty(inner)),
+		Ty::Array(inner, len) => format!("[{}; {}]", canonical_ty(inner), len),
+		Ty::Tuple(types) => format!("({})", types.iter().map(canonical_ty).collect::<Vec<_>>().join(", ")),
+		Ty::Bytes => "Bytes".into(),
+		Ty::Text => "Text".into(),
+		Ty::AccountId => "AccountId".into(),
+		Ty::Hash(len) => format!("[u8; {}]", len),
+		Ty::Call => "Call".into(),
+		Ty::Named(name) => match type_def(name) {
+			Some(TypeDef::Alias(alias)) => canonical(alias),
+			_ => name.clone(),
+		},
+		Ty::Generic(name, params) =>
+			format!("{}<{}>", name, params.iter().map(canonical_ty).collect::<Vec<_>>().join(", ")),
+	}
+}
+
+/// SCALE encode `value` as `ty`.
+pub fn encode(ty: &str, value: &Value) -> Result<Vec<u8>, String> {
+	let mut out = Vec::new();
+	encode_to(&parse(ty)?, value, &mut out)?;
+	Ok(out)
+}
+
+fn hex_bytes(s: &str) -> Result<Vec<u8>, String> {
+	let hex = s.strip_prefix("0x").ok_or_else(|| format!("Expected 0x-prefixed hex, got `{}`", s))?;
+	hex::decode(hex).map_err(|e| format!("Invalid hex `{}`: {}", s, e))
+}
+
+fn uint(value: &Value) -> Result<u128, String> {
+	match value {
+		Value::Number(n) => n.as_u64().map(u128::from),
+		Value::String(s) if s.starts_with("0x") => u128::from_str_radix(&s[2..], 16).ok(),
+		Value::String(s) => s.parse().ok(),
+		_ => None,
+	}.ok_or_else(|| format!("Expected an unsigned integer, got {}", value))
+}
+
+fn int(value: &Value) -> Result<i128, String> {
+	match value {
+		Value::Number(n) => n.as_i64().map(i128::from),
+		Value::String(s) => s.parse().ok(),
+		_ => None,
+	}.ok_or_else(|| format!("Expected an integer, got {}", value))
+}
+
+fn bytes(value: &Value) -> Result<Vec<u8>, String> {
+	match value {
+		Value::String(s) if s.starts_with("0x") => hex_bytes(s),
+		Value::String(s) => Ok(s.as_bytes().to_vec()),
+		Value::Array(items) => items.iter()
+			.map(|item| uint(item).and_then(|b| if b <= 255 { Ok(b as u8) } else { Err(format!("{} is not a byte", b)) }))
+			.collect(),
+		_ => Err(format!("Expected hex, text or a byte array, got {}", value)),
+	}
+}
+
+fn fixed_bytes(value: &Value, len: usize) -> Result<Vec<u8>, String> {
+	let bytes = bytes(value)?;
+	if bytes.len() != len {
+		return Err(format!("Expected {} bytes, got {}", len, bytes.len()));
+	}
+	Ok(bytes)
+}
+
+fn account_id(value: &Value) -> Result<AccountId32, String> {
+	let s = value.as_str().ok_or_else(|| format!("Expected an SS58 address or hex, got {}", value))?;
+	if let Ok(account) = AccountId32::from_ss58check(s) {
+		return Ok(account);
+	}
+
+	let bytes = fixed_bytes(value, 32)?;
+	let mut raw = [0u8; 32];
+	raw.copy_from_slice(&bytes);
+	Ok(raw.into())
+}
+
+fn encode_to(ty: &Ty, value: &Value, out: &mut Vec<u8>) -> Result<(), String> {
+	if let Some(raw) = value.as_str().and_then(|s| s.strip_prefix("scale:")) {
+		out.extend(hex_bytes(raw)?);
+		return Ok(());
+	}
+
+	match ty {
+		Ty::Bool => match value {
+			Value::Bool(b) => b.encode_to(out),
+			Value::String(s) if s == "true" || s == "false" => (s == "true").encode_to(out),
+			_ => return Err(format!("Expected a bool, got {}", value)),
+		},
+		Ty::Uint(bits) => {
+			let n = uint(value)?;
+			if *bits < 128 && n >> bits != 0 {
+				return Err(format!("{} doesn't fit in u{}", n, bits));
+			}
+			out.extend_from_slice(&n.to_le_bytes()[..bits / 8]);
+		},
+		Ty::Int(bits) => {
+			let n = int(value)?;
+			let bound = 1i128.checked_shl(*bits as u32 - 1).unwrap_or(i128::MAX);
+			if *bits < 128 && (n >= bound || n < -bound) {
+				return Err(format!("{} doesn't fit in i{}", n, bits));
+			}
+			out.extend_from_slice(&n.to_le_bytes()[..bits / 8]);
+		},
+		Ty::Compact(_) => Compact(uint(value)?).encode_to(out),
+		Ty::Bytes => bytes(value)?.encode_to(out),
+		Ty::Text => value.as_str()
+			.ok_or_else(|| format!("Expected text, got {}", value))?
+			.encode_to(out),
+		Ty::Vec(inner) => {
+			let items = value.as_array().ok_or_else(|| format!("Expected an array, got {}", value))?;
+			Compact(items.len() as u32).encode_to(out);
+			for item in items {
+				encode_to(inner, item, out)?;
+			}
+		},
+		Ty::Option(inner) => match value {
+			Value::Null => out.push(0),
+			value => {
+				out.push(1);
+				encode_to(inner, value, out)?;
+			},
+		},
+		Ty::Array(inner, len) if **inner == Ty::Uint(8) => out.extend(fixed_bytes(value, *len)?),
+		Ty::Array(inner, len) => {
+			let items = value.as_array()
+				.filter(|items| items.len() == *len)
+				.ok_or_else(|| format!("Expected an array of {} items, got {}", len, value))?;
+			for item in items {
+				encode_to(inner, item, out)?;
+			}
+		},
+		Ty::Tuple(types) if types.len() == 1 && !value.is_array() => encode_to(&types[0], value, out)?,
+		Ty::Tuple(types) => {
+			let items = value.as_array()
+				.filter(|items| items.len() == types.len())
+				.ok_or_else(|| format!("Expected an array of {} items, got {}", types.len(), value))?;
+			for (ty, item) in types.iter().zip(items) {
+				encode_to(ty, item, out)?;
+			}
+		},
+		Ty::AccountId => account_id(value)?.encode_to(out),
+		Ty::Hash(len) => out.extend(fixed_bytes(value, *len)?),
+		Ty::Call => out.extend(
+			value.as_str()
+				.ok_or_else(|| format!("Expected a hex encoded call, got {}", value))
+				.and_then(hex_bytes)?
+		),
+		Ty::Named(name) => match type_def(name) {
+			Some(TypeDef::Alias(alias)) => encode_to(&parse(alias)?, value, out)?,
+			Some(TypeDef::Struct(fields)) => {
+				let object = value.as_object()
+					.ok_or_else(|| format!("Expected a `{}` object, got {}", name, value))?;
+				for (field, ty) in fields.iter() {
+					let field_value = object.get(*field)
+						.ok_or_else(|| format!("Missing field `{}` of `{}`", field, name))?;
+					encode_to(&parse(ty)?, field_value, out)
+						.map_err(|e| format!("Field `{}` of `{}`: {}", field, name, e))?;
+				}
+			},
+			Some(TypeDef::Enum(variants)) => {
+				let (variant, payload) = match value {
+					Value::String(variant) => (variant.as_str(), &Value::Null),
+					Value::Object(object) if object.len() == 1 => {
+						let (variant, payload) = object.iter().next().expect("object has one entry; qed");
+						(variant.as_str(), payload)
+					},
+					_ => return Err(format!("Expected a `{}` variant, got {}", name, value)),
+				};
+				let (index, (_, payload_ty)) = variants.iter()
+					.enumerate()
+					.find(|(_, (v, _))| v.eq_ignore_ascii_case(variant))
+					.ok_or_else(|| format!("No variant `{}` in `{}`", variant, name))?;
+
+				out.push(index as u8);
+				if let Some(payload_ty) = payload_ty {
+					encode_to(&parse(payload_ty)?, payload, out)?;
+				}
+			},
+			None => return Err(format!("Unsupported type `{}`; pass the value as `scale:0x..`", name)),
+		},
+		Ty::Generic(name, _) =>
+			return Err(format!("Unsupported generic type `{}`; pass the value as `scale:0x..`", name)),
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame_support::{traits::BalanceStatus, weights::{DispatchClass, DispatchInfo, Pays}};
+	use litentry_runtime::{Call, Event, Runtime, ProxyType, SignedExtra, UncheckedExtrinsic};
+	use pallet_democracy::{AccountVote, Conviction, Vote};
+	use sp_core::{ecdsa, sr25519, H256};
+	use sp_runtime::{DispatchError, MultiSignature};
+
+	fn metadata() -> Metadata {
+		Metadata::native().expect("the native runtime has V12 metadata; qed")
+	}
+
+	fn account(byte: u8) -> AccountId32 {
+		AccountId32::new([byte; 32])
+	}
+
+	/// Decode the `Encode` output of `value` as `ty`, and encode the result back.
+	fn round_trip<T: Encode>(metadata: &Metadata, ty: &str, value: T, expected: Value) {
+		let encoded = value.encode();
+		let decoded = metadata.decode(ty, &encoded).unwrap_or_else(|e| panic!("decoding `{}`: {}", ty, e));
+		assert_eq!(decoded, expected, "decoding `{}`", ty);
+		assert_eq!(encode(ty, &decoded), Ok(encoded), "encoding `{}`", ty);
+	}
+
+	#[test]
+	fn runtime_types_round_trip() {
+		let metadata = metadata();
+		round_trip(&metadata, "ProxyType", ProxyType::Governance, json!("Governance"));
+		round_trip(&metadata, "Conviction", Conviction::Locked6x, json!("Locked6x"));
+		round_trip(&metadata, "DispatchClass", DispatchClass::Mandatory, json!("Mandatory"));
+		round_trip(&metadata, "Pays", Pays::No, json!("No"));
+		round_trip(&metadata, "BalanceStatus", BalanceStatus::Reserved, json!("Reserved"));
+		round_trip(
+			&metadata,
+			"DispatchInfo",
+			DispatchInfo { weight: 10, class: DispatchClass::Operational, pays_fee: Pays::No },
+			json!({ "weight": 10, "class": "Operational", "paysFee": "No" }),
+		);
+		round_trip(
+			&metadata,
+			"AccountVote<BalanceOf<T>>",
+			AccountVote::Standard { vote: Vote { aye: true, conviction: Conviction::Locked2x }, balance: 100u128 },
+			json!({ "Standard": { "vote": 0x82, "balance": "100" } }),
+		);
+		round_trip(
+			&metadata,
+			"AccountVote<BalanceOf<T>>",
+			AccountVote::Split { aye: 1u128, nay: u128::MAX },
+			json!({ "Split": { "aye": "1", "nay": u128::MAX.to_string() } }),
+		);
+		round_trip(
+			&metadata,
+			"MultiSignature",
+			MultiSignature::Sr25519(sr25519::Signature::from_raw([7; 64])),
+			json!({ "Sr25519": format!("0x{}", hex::encode([7u8; 64])) }),
+		);
+		round_trip(
+			&metadata,
+			"MultiSignature",
+			MultiSignature::Ecdsa(ecdsa::Signature::from_raw([9; 65])),
+			json!({ "Ecdsa": format!("0x{}", hex::encode([9u8; 65])) }),
+		);
+		round_trip(&metadata, "DispatchError", DispatchError::BadOrigin, json!("BadOrigin"));
+		round_trip(&metadata, "DispatchError", DispatchError::NoProviders, json!("NoProviders"));
+	}
+
+	#[test]
+	fn module_errors_are_named() {
+		let metadata = metadata();
+		let error = DispatchError::Module { index: 5, error: 3, message: None };
+		let decoded = metadata.decode("DispatchError", &error.encode()).unwrap();
+
+		assert_eq!(decoded["Module"]["pallet"], "Balances");
+		assert_eq!(decoded["Module"]["name"], "InsufficientBalance");
+		assert_eq!(encode("DispatchError", &decoded), Ok(error.encode()));
+	}
+
+	#[test]
+	fn calls_round_trip() {
+		let metadata = metadata();
+		let call = Call::Balances(pallet_balances::Call::transfer(account(2), 12345));
+		let args = [json!(account(2).to_ss58check()), json!("12345")];
+
+		assert_eq!(metadata.encode_call("Balances", "transfer", &args), Ok(call.encode()));
+		assert_eq!(
+			metadata.decode_call(&mut &call.encode()[..]),
+			Ok(json!({
+				"pallet": "Balances",
+				"call": "transfer",
+				"args": { "dest": account(2).to_ss58check(), "value": 12345 },
+			})),
+		);
+	}
+
+	#[test]
+	fn signed_extrinsics_decode() {
+		let call = Call::Balances(pallet_balances::Call::transfer(account(2), 12345));
+		let extra: SignedExtra = (
+			frame_system::CheckSpecVersion::<Runtime>::new(),
+			frame_system::CheckTxVersion::<Runtime>::new(),
+			frame_system::CheckGenesis::<Runtime>::new(),
+			frame_system::CheckEra::<Runtime>::from(Era::Immortal),
+			frame_system::CheckNonce::<Runtime>::from(7),
+			frame_system::CheckWeight::<Runtime>::new(),
+			pallet_transaction_payment::ChargeTransactionPayment::<Runtime>::from(100),
+		);
+		let signature = MultiSignature::Sr25519(sr25519::Signature::from_raw([7; 64]));
+		let extrinsic = UncheckedExtrinsic::new_signed(call, account(1), signature, extra);
+
+		let decoded = metadata().decode_extrinsic(&extrinsic.encode()).unwrap();
+		assert_eq!(decoded["signer"], account(1).to_ss58check());
+		assert_eq!(decoded["nonce"], 7);
+		assert_eq!(decoded["tip"], 100);
+		assert_eq!(decoded["call"]["call"], "transfer");
+		assert_eq!(decoded["call"]["args"]["dest"], account(2).to_ss58check());
+	}
+
+	#[test]
+	fn events_decode() {
+		let info = DispatchInfo { weight: 10, class: DispatchClass::Normal, pays_fee: Pays::Yes };
+		let events = vec![
+			frame_system::EventRecord {
+				phase: frame_system::Phase::ApplyExtrinsic(1),
+				event: Event::pallet_balances(pallet_balances::Event::<Runtime>::Transfer(account(1), account(2), 5)),
+				topics: vec![],
+			},
+			frame_system::EventRecord {
+				phase: frame_system::Phase::ApplyExtrinsic(1),
+				event: Event::frame_system(frame_system::Event::<Runtime>::ExtrinsicFailed(
+					DispatchError::Module { index: 5, error: 3, message: None },
+					info,
+				)),
+				topics: vec![H256::repeat_byte(1)],
+			},
+			frame_system::EventRecord {
+				phase: frame_system::Phase::Finalization,
+				event: Event::frame_system(frame_system::Event::<Runtime>::ExtrinsicSuccess(info)),
+				topics: vec![],
+			},
+		];
+
+		let decoded = metadata().decode_events(&events.encode()).unwrap();
+		assert_eq!(decoded[0], json!({
+			"phase": { "ApplyExtrinsic": 1 },
+			"pallet": "Balances",
+			"event": "Transfer",
+			"args": [account(1).to_ss58check(), account(2).to_ss58check(), "5"],
+			"topics": [],
+		}));
+		assert_eq!(decoded[1]["event"], "ExtrinsicFailed");
+		assert_eq!(decoded[1]["args"][0]["Module"]["name"], "InsufficientBalance");
+		assert_eq!(decoded[1]["args"][1], json!({ "weight": 10, "class": "Normal", "paysFee": "Yes" }));
+		assert_eq!(decoded[1]["topics"], json!([format!("0x{}", hex::encode([1u8; 32]))]));
+		assert_eq!(decoded[2]["phase"], "Finalization");
+		assert_eq!(decoded[2]["event"], "ExtrinsicSuccess");
+	}
+
+	#[test]
+	fn undecodable_events_stay_encoded() {
+		let transfer = frame_system::EventRecord {
+			phase: frame_system::Phase::ApplyExtrinsic(0),
+			event: Event::pallet_balances(pallet_balances::Event::<Runtime>::Transfer(account(1), account(2), 5)),
+			topics: vec![],
+		};
+		let success = frame_system::EventRecord {
+			phase: frame_system::Phase::ApplyExtrinsic(0),
+			event: Event::frame_system(frame_system::Event::<Runtime>::ExtrinsicSuccess(Default::default())),
+			topics: vec![],
+		};
+		let first = transfer.encode().len();
+		// Without the topics of the last event.
+		let mut encoded = vec![transfer, success].encode();
+		encoded.pop();
+
+		let events = metadata().decode_events_lossy(&encoded).unwrap();
+		assert_eq!(events.decoded.len(), 1);
+		assert_eq!(events.decoded[0]["event"], "Transfer");
+
+		let undecoded = events.undecoded.expect("the second event is cut short");
+		assert_eq!((undecoded.index, undecoded.count), (1, 1));
+		assert_eq!(undecoded.header, json!({ "phase": { "ApplyExtrinsic": 0 }, "pallet": "System", "event": "ExtrinsicSuccess" }));
+		assert_eq!(undecoded.raw, encoded[1 + first..].to_vec());
+	}
+}
@@ -1,8 +1,9 @@
 //! Service and ServiceFactory implementation. Specialized wrapper over substrate service.
 
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
-use sc_client_api::{ExecutorProvider, RemoteBackend};
+use sc_client_api::{Backend, ExecutorProvider, RemoteBackend};
 use litentry_runtime::{self, opaque::Block, RuntimeApi};
 use sc_service::{error::Error as ServiceError, Configuration, TaskManager};
 use sp_inherents::InherentDataProviders;
@@ -11,6 +12,15 @@ pub use sc_executor::NativeExecutor;
 use sp_consensus_aura::sr25519::{AuthorityPair as AuraPair};
 use sc_finality_grandpa::SharedVoterState;
 use sc_keystore::LocalKeystore;
+use crate::chain_spec::{AuthoringBackoffParameters, GrandpaParameters};
+use crate::authority_keys::KeyMonitor;
+use crate::block_import::ChainSpecBlockImport;
+use crate::health::{self, HealthConfig};
+use crate::index::EventIndexer;
+use crate::metrics::{self, Metrics};
+use crate::ocw::{self, mock::HttpFixtures, ActivitySpawner, OcwActivity, OcwConfig};
+use crate::faucet_endpoint::{self, FaucetEndpointConfig};
+use crate::rpc::{faucet::{Faucet, FaucetAccount, FaucetConfig}, DenyUnsafe};
 
 // Our native executor instance.
 native_executor_instance!(
@@ -91,8 +101,38 @@ fn remote_keystore(_url: &String) -> Result<Arc<LocalKeystore>, &'static str> {
 	Err("Remote Keystore not supported.")
 }
 
+/// Settings of a full node beyond its `Configuration`, from the command line and
+/// the chain spec.
+pub struct NodeOptions {
+	/// GRANDPA parameters, with the command line overrides applied.
+	pub grandpa: GrandpaParameters,
+	/// Aura authoring backoff, if enabled.
+	pub authoring_backoff: Option<AuthoringBackoffParameters>,
+	/// Values to write to offchain storage before the offchain workers start.
+	pub ocw_config: Option<OcwConfig>,
+	/// Fixtures answering the offchain workers' HTTP requests.
+	pub http_fixtures: Option<HttpFixtures>,
+	/// Health and readiness endpoint.
+	pub health: Option<HealthConfig>,
+	/// SQLite database to index the events of finalized blocks into.
+	pub index_events: Option<PathBuf>,
+	/// Token server to probe for the metrics.
+	pub token_server_probe: Option<String>,
+	/// Faucet to serve `faucet_drip` from.
+	pub faucet: Option<FaucetConfig>,
+	/// Endpoint serving `faucet_drip` with per-IP limits.
+	pub faucet_endpoint: Option<FaucetEndpointConfig>,
+}
+
 /// Builds a new service for a full client.
-pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError> {
+pub fn new_full(mut config: Configuration, options: NodeOptions) -> Result<TaskManager, ServiceError> {
+	let NodeOptions {
+		grandpa: grandpa_params, authoring_backoff, ocw_config, http_fixtures, health, index_events,
+		token_server_probe, faucet, faucet_endpoint: endpoint,
+	} = options;
+
+	// Fork and bad blocks from the chain spec are enforced by the client
+	// built in `new_full_parts`.
 	let sc_service::PartialComponents {
 		client, backend, mut task_manager, import_queue, mut keystore_container, select_chain, transaction_pool,
 		inherent_data_providers,
@@ -122,28 +162,129 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 			block_announce_validator_builder: None,
 		})?;
 
-	if config.offchain_worker.enabled {
-		sc_service::build_offchain_workers(
-			&config, backend.clone(), task_manager.spawn_handle(), client.clone(), network.clone(),
+	let ocw_activity = if config.offchain_worker.enabled {
+		if let Some(ocw_config) = ocw_config {
+			let mut storage = backend.offchain_storage().ok_or_else(|| ServiceError::Other(
+				"Offchain worker configured, but the backend has no offchain storage.".into()
+			))?;
+			ocw_config.write_to(&mut storage);
+		}
+
+		let activity = Arc::new(OcwActivity::default());
+		match (http_fixtures, backend.offchain_storage()) {
+			(Some(fixtures), _) => task_manager.spawn_handle().spawn(
+				"offchain-notifications",
+				ocw::run_with_http_fixtures(
+					client.clone(),
+					backend.clone(),
+					Arc::new(fixtures),
+					transaction_pool.clone(),
+					keystore_container.sync_keystore(),
+					config.role.is_authority(),
+					ActivitySpawner::new(client.clone(), task_manager.spawn_handle(), activity.clone()),
+				),
+			),
+			(None, Some(storage)) => task_manager.spawn_handle().spawn(
+				"offchain-notifications",
+				sc_offchain::notification_future(
+					config.role.is_authority(),
+					client.clone(),
+					Arc::new(sc_offchain::OffchainWorkers::new(client.clone(), storage)),
+					ActivitySpawner::new(client.clone(), task_manager.spawn_handle(), activity.clone()),
+					network.clone(),
+				),
+			),
+			(None, None) =>
+				log::warn!("Offchain workers disabled, due to lack of offchain storage support in backend."),
+		}
+		Some(activity)
+	} else {
+		if ocw_config.is_some() || http_fixtures.is_some() {
+			return Err(ServiceError::Other(
+				"Offchain worker settings given, but offchain workers are disabled, see `--offchain-worker`".into()
+			));
+		}
+		None
+	};
+
+	if let Some(registry) = config.prometheus_registry() {
+		task_manager.spawn_handle().spawn(
+			"litentry-metrics",
+			metrics::run::<_, FullBackend>(client.clone(), Metrics::register(registry)?, token_server_probe),
+		);
+	}
+
+	if config.role.is_authority() {
+		let monitor = KeyMonitor::new(
+			client.clone(), keystore_container.sync_keystore(), config.prometheus_registry(),
+		)?;
+		monitor.check();
+		task_manager.spawn_handle().spawn("authority-key-monitor", monitor.run());
+	}
+
+	if let Some(path) = index_events {
+		let indexer = EventIndexer::open(client.clone(), backend.clone(), &path).map_err(ServiceError::Other)?;
+		task_manager.spawn_handle().spawn_blocking("event-index", indexer.follow());
+	}
+
+	if let Some(health) = health {
+		let server = health::bind(&health).map_err(ServiceError::Other)?;
+		task_manager.spawn_handle().spawn(
+			"health-endpoint",
+			health::serve(server, health, client.clone(), network.clone(), ocw_activity),
 		);
 	}
 
 	let role = config.role.clone();
 	let force_authoring = config.force_authoring;
-	let backoff_authoring_blocks: Option<()> = None;
+	let backoff_authoring_blocks = authoring_backoff.map(|params| {
+		sc_consensus_slots::BackoffAuthoringOnFinalizedHeadLagging {
+			max_interval: params.max_interval,
+			unfinalized_slack: params.unfinalized_slack,
+			authoring_bias: params.authoring_bias,
+		}
+	});
 	let name = config.network.node_name.clone();
 	let enable_grandpa = !config.disable_grandpa;
 	let prometheus_registry = config.prometheus_registry().cloned();
 
+	let justification_stream = grandpa_link.justification_stream();
+	let shared_authority_set = grandpa_link.shared_authority_set().clone();
+	let shared_voter_state = SharedVoterState::empty();
+	let finality_proof_provider = sc_finality_grandpa::FinalityProofProvider::new_for_service(
+		backend.clone(),
+		Some(shared_authority_set.clone()),
+	);
+
+	let faucet = faucet
+		.map(|config| FaucetAccount::new(config, keystore_container.sync_keystore()).map(Arc::new))
+		.transpose()
+		.map_err(ServiceError::Other)?;
+	if let (Some(account), Some(endpoint)) = (&faucet, endpoint) {
+		let server = faucet_endpoint::bind(&endpoint).map_err(ServiceError::Other)?;
+		// The endpoint is exposed only as far as `bind` allows.
+		let faucet = Faucet::new(client.clone(), transaction_pool.clone(), DenyUnsafe::No, account.clone());
+		task_manager.spawn_handle().spawn("faucet-endpoint", faucet_endpoint::serve(server, faucet));
+	}
+
 	let rpc_extensions_builder = {
 		let client = client.clone();
 		let pool = transaction_pool.clone();
+		let shared_voter_state = shared_voter_state.clone();
 
-		Box::new(move |deny_unsafe, _| {
+		Box::new(move |deny_unsafe, subscription_executor| {
 			let deps = crate::rpc::FullDeps {
 				client: client.clone(),
 				pool: pool.clone(),
 				deny_unsafe,
+				grandpa: crate::rpc::GrandpaDeps {
+					shared_voter_state: shared_voter_state.clone(),
+					shared_authority_set: shared_authority_set.clone(),
+					justification_stream: justification_stream.clone(),
+					subscription_executor,
+					finality_provider: finality_proof_provider.clone(),
+				},
+				faucet: faucet.clone(),
 			};
 
 			crate::rpc::create_full(deps)
@@ -206,11 +347,10 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 	};
 
 	let grandpa_config = sc_finality_grandpa::Config {
-		// FIXME #1578 make this available through chainspec
-		gossip_duration: Duration::from_millis(333),
-		justification_period: 512,
+		gossip_duration: Duration::from_millis(grandpa_params.gossip_duration_millis),
+		justification_period: grandpa_params.justification_period,
 		name: Some(name),
-		observer_enabled: false,
+		observer_enabled: grandpa_params.observer_enabled,
 		keystore,
 		is_authority: role.is_network_authority(),
 	};
@@ -227,9 +367,9 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 			link: grandpa_link,
 			network,
 			telemetry_on_connect: telemetry_connection_notifier.map(|x| x.on_connect_stream()),
-			voting_rule: sc_finality_grandpa::VotingRulesBuilder::default().build(),
+			voting_rule: grandpa_voting_rule(&grandpa_params),
 			prometheus_registry,
-			shared_voter_state: SharedVoterState::empty(),
+			shared_voter_state,
 		};
 
 		// the GRANDPA voter task is considered infallible, i.e.
@@ -244,6 +384,22 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 	Ok(task_manager)
 }
 
+/// Builds the GRANDPA voting rules selected in the chain spec.
+fn grandpa_voting_rule(
+	params: &GrandpaParameters,
+) -> impl sc_finality_grandpa::VotingRule<Block, FullClient> + Clone {
+	let mut builder = sc_finality_grandpa::VotingRulesBuilder::new();
+
+	if params.before_best_block_by > 0 {
+		builder = builder.add(sc_finality_grandpa::BeforeBestBlockBy(params.before_best_block_by));
+	}
+	if params.three_quarters_of_unfinalized_chain {
+		builder = builder.add(sc_finality_grandpa::ThreeQuartersOfTheUnfinalizedChain);
+	}
+
+	builder.build()
+}
+
 /// Builds a new service for a light client.
 pub fn new_light(mut config: Configuration) -> Result<TaskManager, ServiceError> {
 	let (client, backend, keystore_container, mut task_manager, on_demand) =
@@ -267,8 +423,17 @@ pub fn new_light(mut config: Configuration) -> Result<TaskManager, ServiceError>
 		select_chain.clone(),
 	)?;
 
+	// The light client isn't built with the chain spec's fork and bad blocks,
+	// so they are enforced in front of the import pipeline instead.
+	let fork_blocks = sc_chain_spec::get_extension::<sc_client_api::ForkBlocks<Block>>(
+		config.chain_spec.extensions(),
+	).cloned().unwrap_or_default();
+	let bad_blocks = sc_chain_spec::get_extension::<sc_client_api::BadBlocks<Block>>(
+		config.chain_spec.extensions(),
+	).cloned().unwrap_or_default();
+
 	let aura_block_import = sc_consensus_aura::AuraBlockImport::<_, _, _, AuraPair>::new(
-		grandpa_block_import.clone(),
+		ChainSpecBlockImport::new(grandpa_block_import.clone(), fork_blocks, bad_blocks),
 		client.clone(),
 	);
 
@@ -300,12 +465,21 @@ pub fn new_light(mut config: Configuration) -> Result<TaskManager, ServiceError>
 		);
 	}
 
+	let light_deps = crate::rpc::LightDeps {
+		remote_blockchain: backend.remote_blockchain(),
+		fetcher: on_demand.clone(),
+		client: client.clone(),
+		pool: transaction_pool.clone(),
+	};
+
+	let rpc_extensions = crate::rpc::create_light(light_deps);
+
 	sc_service::spawn_tasks(sc_service::SpawnTasksParams {
 		remote_blockchain: Some(backend.remote_blockchain()),
 		transaction_pool,
 		task_manager: &mut task_manager,
 		on_demand: Some(on_demand),
-		rpc_extensions_builder: Box::new(|_, _| ()),
+		rpc_extensions_builder: Box::new(sc_service::NoopRpcExtensionBuilder(rpc_extensions)),
 		config,
 		client,
 		keystore: keystore_container.sync_keystore(),
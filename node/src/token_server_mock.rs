@@ -0,0 +1,219 @@
+//! `token-server-mock`: a stand-in for `litentry-token-server` and the balance
+//! APIs the offchain worker queries, answering from a JSON fixture so tools
+//! needing a live endpoint can run without network access. The offchain worker
+//! itself is served by `--offchain-http-mock` instead.
+//!
+//! Served endpoints:
+//! - `GET /`: the API keys, like `litentry-token-server`.
+//! - `GET /api?module=account&action=balance|balancemulti&address=..`: etherscan.
+//! - `POST /v3/<key>`: infura `eth_getBalance`, single or batched.
+//! - `GET /balance?active=a|b`: blockchain.info.
+
+use std::{collections::BTreeMap, convert::Infallible, net::SocketAddr, path::{Path, PathBuf}, sync::Arc};
+use hyper::{
+	service::{make_service_fn, service_fn},
+	Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use structopt::StructOpt;
+use sc_cli::Error;
+
+/// Contents of the `--fixture` file.
+///
+/// ```json
+/// {
+///   "tokens": { "etherscan": "key", "infura": "key", "blockchain": "" },
+///   "ethereum": { "0x4d88dc5d528a33e4b8be579e9476715f60060582": "5000000000000000000" },
+///   "bitcoin": { "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa": 100000000 }
+/// }
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fixture {
+	/// API keys returned by `GET /`, keyed by service name.
+	#[serde(default)]
+	pub tokens: BTreeMap<String, String>,
+	/// Ethereum balances in wei, as decimal strings, keyed by address.
+	#[serde(default)]
+	pub ethereum: BTreeMap<String, String>,
+	/// Bitcoin balances in satoshis, keyed by address.
+	#[serde(default)]
+	pub bitcoin: BTreeMap<String, u64>,
+}
+
+impl Fixture {
+	/// Parse the fixture at `path`.
+	pub fn load(path: &Path) -> Result<Self, String> {
+		let content = std::fs::read(path)
+			.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+		let mut fixture: Fixture = serde_json::from_slice(&content)
+			.map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+
+		// Ethereum addresses are matched regardless of checksum casing.
+		fixture.ethereum = fixture.ethereum.into_iter()
+			.map(|(address, balance)| {
+				balance.parse::<u128>()
+					.map(|_| (address.to_lowercase(), balance.clone()))
+					.map_err(|e| format!("Invalid balance {} of {}: {}", balance, address, e))
+			})
+			.collect::<Result<_, _>>()?;
+
+		Ok(fixture)
+	}
+
+	fn ethereum_balance(&self, address: &str) -> u128 {
+		self.ethereum.get(&address.to_lowercase())
+			.map(|balance| balance.parse().expect("balances are validated on load; qed"))
+			.unwrap_or_default()
+	}
+
+	fn bitcoin_balance(&self, address: &str) -> u64 {
+		self.bitcoin.get(address).copied().unwrap_or_default()
+	}
+}
+
+#[derive(Debug, StructOpt)]
+pub struct TokenServerMockCmd {
+	/// JSON fixture with the API keys and the balances to serve.
+	#[structopt(long, value_name = "PATH", parse(from_os_str))]
+	pub fixture: Option<PathBuf>,
+
+	/// Address to listen on. The default is the one `litentry-token-server` uses.
+	#[structopt(long, value_name = "ADDR", default_value = "127.0.0.1:4000")]
+	pub listen: SocketAddr,
+}
+
+impl TokenServerMockCmd {
+	/// Run the server until the process is stopped.
+	pub fn run(&self) -> Result<(), Error> {
+		let fixture = Arc::new(match &self.fixture {
+			Some(path) => Fixture::load(path)?,
+			None => Default::default(),
+		});
+		let mut runtime = tokio::runtime::Runtime::new()?;
+
+		runtime.block_on(async {
+			let service = make_service_fn(move |_| {
+				let fixture = fixture.clone();
+				async move {
+					Ok::<_, Infallible>(service_fn(move |request| handle(fixture.clone(), request)))
+				}
+			});
+			let server = Server::try_bind(&self.listen)
+				.map_err(|e| format!("Failed to listen on {}: {}", self.listen, e))?
+				.serve(service);
+
+			println!("Token server mock listening on http://{}", self.listen);
+			server.await.map_err(|e| Error::Other(format!("Token server mock failed: {}", e)))
+		})
+	}
+}
+
+async fn handle(fixture: Arc<Fixture>, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+	let method = request.method().clone();
+	let path = request.uri().path().to_string();
+	let query = query_params(request.uri().query());
+	log::debug!("{} {}", method, request.uri());
+
+	let response = match (&method, path.as_str()) {
+		(&Method::GET, "/") => json_response(json!(fixture.tokens)),
+		(&Method::GET, "/api") => etherscan(&fixture, &query),
+		(&Method::GET, "/balance") => blockchain(&fixture, &query),
+		(&Method::POST, path) if path.starts_with("/v3/") => {
+			match hyper::body::to_bytes(request.into_body()).await {
+				Ok(body) => infura(&fixture, &body),
+				Err(e) => error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+			}
+		},
+		_ => error_response(StatusCode::NOT_FOUND, "Not found"),
+	};
+
+	Ok(response)
+}
+
+fn query_params(query: Option<&str>) -> BTreeMap<String, String> {
+	url::form_urlencoded::parse(query.unwrap_or_default().as_bytes())
+		.into_owned()
+		.collect()
+}
+
+fn json_response(body: Value) -> Response<Body> {
+	Response::builder()
+		.header("Content-Type", "application/json")
+		.body(Body::from(body.to_string()))
+		.expect("static response parts are valid; qed")
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Body> {
+	let mut response = json_response(json!({ "error": message }));
+	*response.status_mut() = status;
+	response
+}
+
+fn etherscan(fixture: &Fixture, query: &BTreeMap<String, String>) -> Response<Body> {
+	let address = query.get("address").map(String::as_str).unwrap_or_default();
+
+	match (query.get("module").map(String::as_str), query.get("action").map(String::as_str)) {
+		(Some("account"), Some("balance")) => json_response(json!({
+			"status": "1",
+			"message": "OK",
+			"result": fixture.ethereum_balance(address).to_string(),
+		})),
+		(Some("account"), Some("balancemulti")) => {
+			let result = address.split(',')
+				.map(|address| json!({
+					"account": address,
+					"balance": fixture.ethereum_balance(address).to_string(),
+				}))
+				.collect::<Vec<_>>();
+
+			json_response(json!({ "status": "1", "message": "OK", "result": result }))
+		},
+		_ => json_response(json!({ "status": "0", "message": "NOTOK", "result": "Unsupported action" })),
+	}
+}
+
+fn blockchain(fixture: &Fixture, query: &BTreeMap<String, String>) -> Response<Body> {
+	let balances = query.get("active").map(String::as_str).unwrap_or_default()
+		.split('|')
+		.filter(|address| !address.is_empty())
+		.map(|address| {
+			let balance = fixture.bitcoin_balance(address);
+			(address.to_string(), json!({ "final_balance": balance, "n_tx": 0, "total_received": balance }))
+		})
+		.collect::<serde_json::Map<_, _>>();
+
+	json_response(Value::Object(balances))
+}
+
+fn infura(fixture: &Fixture, body: &[u8]) -> Response<Body> {
+	match serde_json::from_slice::<Value>(body) {
+		Ok(Value::Array(calls)) =>
+			json_response(Value::Array(calls.iter().map(|call| eth_call(fixture, call)).collect())),
+		Ok(call) => json_response(eth_call(fixture, &call)),
+		Err(e) => json_response(json!({
+			"jsonrpc": "2.0",
+			"id": null,
+			"error": { "code": -32700, "message": format!("Parse error: {}", e) },
+		})),
+	}
+}
+
+fn eth_call(fixture: &Fixture, call: &Value) -> Value {
+	let id = call.get("id").cloned().unwrap_or(Value::Null);
+	let address = call.get("params").and_then(|params| params.get(0)).and_then(Value::as_str);
+
+	match (call.get("method").and_then(Value::as_str), address) {
+		(Some("eth_getBalance"), Some(address)) => json!({
+			"jsonrpc": "2.0",
+			"id": id,
+			"result": format!("{:#x}", fixture.ethereum_balance(address)),
+		}),
+		_ => json!({
+			"jsonrpc": "2.0",
+			"id": id,
+			"error": { "code": -32601, "message": "Method not supported by the mock" },
+		}),
+	}
+}
@@ -0,0 +1,395 @@
+//! `tx` subcommands: build, sign and submit transactions in separate steps, so
+//! signing can happen on a machine that is never online.
+//!
+//! `tx build` writes an unsigned transaction as JSON, `tx sign` turns it into a
+//! signed extrinsic and `tx submit` sends that to a node.
+
+use std::{
+	fs,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+use codec::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use structopt::StructOpt;
+use sc_cli::{CryptoScheme, Error};
+use sc_keystore::LocalKeystore;
+use sp_core::{
+	crypto::{CryptoTypePublicPair, KeyTypeId, Pair, Ss58Codec},
+	ecdsa, ed25519, hexdisplay::HexDisplay, sr25519, Bytes,
+};
+use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
+use sp_runtime::{generic::Era, traits::IdentifyAccount, MultiSignature, MultiSigner};
+use litentry_runtime::{
+	AccountId, Balance, Call, Hash, Index, Runtime, SignedExtra, SignedPayload, UncheckedExtrinsic, VERSION,
+};
+
+use crate::scale::Metadata;
+
+#[derive(Debug, StructOpt)]
+pub enum TxSubcommand {
+	/// Encode a call and the signed extensions into an unsigned transaction.
+	Build(BuildCmd),
+
+	/// Sign an unsigned transaction.
+	Sign(SignCmd),
+
+	/// Send a signed transaction to a node.
+	Submit(SubmitCmd),
+}
+
+impl TxSubcommand {
+	/// Run the tx subcommand.
+	pub fn run(&self) -> Result<(), Error> {
+		match self {
+			TxSubcommand::Build(cmd) => cmd.run(),
+			TxSubcommand::Sign(cmd) => cmd.run(),
+			TxSubcommand::Submit(cmd) => cmd.run(),
+		}
+	}
+}
+
+/// A transaction waiting to be signed, with everything covered by the signature.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct UnsignedTransaction {
+	/// SCALE encoded call.
+	pub call: Bytes,
+	/// Nonce of the signing account.
+	pub nonce: Index,
+	/// Tip for the block author.
+	pub tip: Balance,
+	/// Era the transaction is valid in.
+	pub era: Era,
+	/// Genesis hash of the chain.
+	pub genesis_hash: Hash,
+	/// Hash of the block the era starts at. The genesis hash for immortal transactions.
+	pub block_hash: Hash,
+	/// Runtime spec version.
+	pub spec_version: u32,
+	/// Runtime transaction version.
+	pub transaction_version: u32,
+}
+
+impl UnsignedTransaction {
+	fn decode_call(&self) -> Result<Call, Error> {
+		Call::decode(&mut &self.call[..])
+			.map_err(|e| format!("Call doesn't decode with the native runtime: {:?}", e).into())
+	}
+
+	fn extra(&self) -> SignedExtra {
+		(
+			frame_system::CheckSpecVersion::<Runtime>::new(),
+			frame_system::CheckTxVersion::<Runtime>::new(),
+			frame_system::CheckGenesis::<Runtime>::new(),
+			frame_system::CheckEra::<Runtime>::from(self.era),
+			frame_system::CheckNonce::<Runtime>::from(self.nonce),
+			frame_system::CheckWeight::<Runtime>::new(),
+			pallet_transaction_payment::ChargeTransactionPayment::<Runtime>::from(self.tip),
+		)
+	}
+
+	/// The payload the signer signs.
+	fn payload(&self) -> Result<SignedPayload, Error> {
+		Ok(SignedPayload::from_raw(
+			self.decode_call()?,
+			self.extra(),
+			(
+				self.spec_version,
+				self.transaction_version,
+				self.genesis_hash,
+				self.block_hash,
+				(),
+				(),
+				(),
+			),
+		))
+	}
+}
+
+#[derive(Debug, StructOpt)]
+pub struct BuildCmd {
+	/// Pallet of the call, e.g. `Balances`.
+	pub pallet: String,
+
+	/// Name of the call, e.g. `transfer`.
+	pub call: String,
+
+	/// Call arguments. JSON values are taken as such, anything else as a string;
+	/// `scale:0x..` passes a pre-encoded value.
+	pub args: Vec<String>,
+
+	/// Nonce of the signing account.
+	#[structopt(long)]
+	pub nonce: Index,
+
+	/// Tip for the block author.
+	#[structopt(long, default_value = "0")]
+	pub tip: Balance,
+
+	/// Genesis hash of the target chain.
+	#[structopt(long)]
+	pub genesis_hash: Hash,
+
+	/// Make the transaction mortal for this many blocks, starting at `--block-number`.
+	#[structopt(long, value_name = "BLOCKS", requires_all = &["block-number", "block-hash"])]
+	pub mortal: Option<u64>,
+
+	/// Number of the block a mortal transaction's era starts at.
+	#[structopt(long)]
+	pub block_number: Option<u64>,
+
+	/// Hash of the block a mortal transaction's era starts at.
+	#[structopt(long)]
+	pub block_hash: Option<Hash>,
+
+	/// Runtime spec version. Defaults to the native runtime's.
+	#[structopt(long)]
+	pub spec_version: Option<u32>,
+
+	/// Runtime transaction version. Defaults to the native runtime's.
+	#[structopt(long)]
+	pub transaction_version: Option<u32>,
+
+	/// Write the unsigned transaction here instead of to stdout.
+	#[structopt(long, short, parse(from_os_str))]
+	pub output: Option<PathBuf>,
+}
+
+impl BuildCmd {
+	/// Run the command.
+	pub fn run(&self) -> Result<(), Error> {
+		let args = self.args.iter()
+			.map(|arg| serde_json::from_str(arg).unwrap_or_else(|_| Value::String(arg.clone())))
+			.collect::<Vec<_>>();
+		let call = Metadata::native()?.encode_call(&self.pallet, &self.call, &args)?;
+
+		let (era, block_hash) = match (self.mortal, self.block_number, self.block_hash) {
+			(Some(period), Some(number), Some(hash)) => (Era::mortal(period, number), hash),
+			_ => (Era::Immortal, self.genesis_hash),
+		};
+		let tx = UnsignedTransaction {
+			call: call.into(),
+			nonce: self.nonce,
+			tip: self.tip,
+			era,
+			genesis_hash: self.genesis_hash,
+			block_hash,
+			spec_version: self.spec_version.unwrap_or(VERSION.spec_version),
+			transaction_version: self.transaction_version.unwrap_or(VERSION.transaction_version),
+		};
+		eprintln!("Call: {:?}", tx.decode_call()?);
+		eprintln!("Signing payload: 0x{}", HexDisplay::from(&tx.payload()?.encode()));
+
+		let json = serde_json::to_string_pretty(&tx).map_err(|e| e.to_string())?;
+		write_output(self.output.as_deref(), &json)
+	}
+}
+
+#[derive(Debug, StructOpt)]
+pub struct SignCmd {
+	/// Unsigned transaction written by `tx build`.
+	#[structopt(parse(from_os_str))]
+	pub transaction: PathBuf,
+
+	/// Secret URI of the signer, e.g. `//Alice` or a mnemonic phrase.
+	#[structopt(long, conflicts_with_all = &["secret-file", "keystore-path"])]
+	pub suri: Option<String>,
+
+	/// File holding the secret URI of the signer.
+	#[structopt(long, parse(from_os_str), conflicts_with = "keystore-path")]
+	pub secret_file: Option<PathBuf>,
+
+	/// Sign with a key from this keystore directory.
+	#[structopt(long, parse(from_os_str), requires = "public")]
+	pub keystore_path: Option<PathBuf>,
+
+	/// Public key or SS58 address of the keystore key to sign with.
+	#[structopt(long)]
+	pub public: Option<String>,
+
+	/// File holding the keystore password.
+	#[structopt(long, parse(from_os_str))]
+	pub password_filename: Option<PathBuf>,
+
+	/// Key type of the keystore key.
+	#[structopt(long, default_value = "acco")]
+	pub key_type: String,
+
+	/// Signature scheme of the signer.
+	#[structopt(
+		long,
+		value_name = "SCHEME",
+		possible_values = &CryptoScheme::variants(),
+		case_insensitive = true,
+		default_value = "Sr25519",
+	)]
+	pub scheme: CryptoScheme,
+
+	/// Write the signed extrinsic here instead of to stdout.
+	#[structopt(long, short, parse(from_os_str))]
+	pub output: Option<PathBuf>,
+}
+
+impl SignCmd {
+	/// Run the command.
+	pub fn run(&self) -> Result<(), Error> {
+		let content = fs::read(&self.transaction)?;
+		let tx: UnsignedTransaction = serde_json::from_slice(&content)
+			.map_err(|e| format!("Invalid transaction in {}: {}", self.transaction.display(), e))?;
+		let payload = tx.payload()?;
+
+		let (signer, signature) = payload.using_encoded(|message| self.sign(message))?;
+		let account: AccountId = signer.into_account();
+		eprintln!("Signer: {}", account.to_ss58check());
+
+		let (call, extra, _) = payload.deconstruct();
+		let extrinsic = UncheckedExtrinsic::new_signed(call, account, signature, extra);
+
+		write_output(self.output.as_deref(), &format!("0x{}", HexDisplay::from(&extrinsic.encode())))
+	}
+
+	fn sign(&self, message: &[u8]) -> Result<(MultiSigner, MultiSignature), Error> {
+		let suri = match (&self.suri, &self.secret_file, &self.keystore_path) {
+			(Some(suri), _, _) => suri.clone(),
+			(None, Some(path), _) => fs::read_to_string(path)?.trim().to_string(),
+			(None, None, Some(path)) => return self.sign_with_keystore(path, message),
+			(None, None, None) => return Err("One of `--suri`, `--secret-file` or `--keystore-path` is required".into()),
+		};
+
+		Ok(match self.scheme {
+			CryptoScheme::Sr25519 => sign_with_pair::<sr25519::Pair>(&suri, message)?,
+			CryptoScheme::Ed25519 => sign_with_pair::<ed25519::Pair>(&suri, message)?,
+			CryptoScheme::Ecdsa => sign_with_pair::<ecdsa::Pair>(&suri, message)?,
+		})
+	}
+
+	fn sign_with_keystore(&self, path: &Path, message: &[u8]) -> Result<(MultiSigner, MultiSignature), Error> {
+		let password = match &self.password_filename {
+			Some(file) => fs::read_to_string(file)?.trim().parse().ok(),
+			None => None,
+		};
+		let keystore: SyncCryptoStorePtr = Arc::new(
+			LocalKeystore::open(path, password).map_err(|e| format!("Failed to open keystore: {}", e))?,
+		);
+		let key_type = key_type(&self.key_type)?;
+		let public = self.public.as_deref().expect("`--public` is required with `--keystore-path`; qed");
+
+		let (signer, public_pair): (MultiSigner, CryptoTypePublicPair) = match self.scheme {
+			CryptoScheme::Sr25519 => {
+				let public = parse_public::<sr25519::Public>(public)?;
+				(MultiSigner::from(public), public.into())
+			},
+			CryptoScheme::Ed25519 => {
+				let public = parse_public::<ed25519::Public>(public)?;
+				(MultiSigner::from(public), public.into())
+			},
+			CryptoScheme::Ecdsa => {
+				let public = parse_public::<ecdsa::Public>(public)?;
+				(MultiSigner::from(public), public.into())
+			},
+		};
+
+		let raw = SyncCryptoStore::sign_with(&*keystore, key_type, &public_pair, message)
+			.map_err(|e| format!("Keystore failed to sign: {:?}", e))?;
+		let signature = match self.scheme {
+			CryptoScheme::Sr25519 => sr25519::Signature::from_slice(&raw).into(),
+			CryptoScheme::Ed25519 => ed25519::Signature::from_slice(&raw).into(),
+			CryptoScheme::Ecdsa => ecdsa::Signature::from_slice(&raw).into(),
+		};
+
+		Ok((signer, signature))
+	}
+}
+
+fn sign_with_pair<P>(suri: &str, message: &[u8]) -> Result<(MultiSigner, MultiSignature), Error> where
+	P: Pair,
+	MultiSigner: From<P::Public>,
+	MultiSignature: From<P::Signature>,
+{
+	let pair = P::from_string(suri, None).map_err(|e| format!("Invalid secret URI: {:?}", e))?;
+	Ok((pair.public().into(), pair.sign(message).into()))
+}
+
+pub(crate) fn parse_public<P: Ss58Codec + Default + AsMut<[u8]>>(s: &str) -> Result<P, Error> {
+	if let Ok(public) = P::from_ss58check(s) {
+		return Ok(public);
+	}
+
+	let bytes = hex::decode(s.trim_start_matches("0x"))
+		.map_err(|_| format!("`{}` is neither an SS58 address nor a hex public key", s))?;
+	let mut public = P::default();
+	if public.as_mut().len() != bytes.len() {
+		return Err(format!("`{}` has the wrong length for a public key", s).into());
+	}
+	public.as_mut().copy_from_slice(&bytes);
+	Ok(public)
+}
+
+fn key_type(s: &str) -> Result<KeyTypeId, Error> {
+	match s.as_bytes() {
+		[a, b, c, d] => Ok(KeyTypeId([*a, *b, *c, *d])),
+		_ => Err(format!("Key type `{}` is not 4 characters", s).into()),
+	}
+}
+
+#[derive(Debug, StructOpt)]
+pub struct SubmitCmd {
+	/// Signed extrinsic, as hex or a file written by `tx sign`.
+	pub extrinsic: String,
+
+	/// HTTP RPC endpoint of the node.
+	#[structopt(long, default_value = "http://127.0.0.1:9933")]
+	pub url: String,
+}
+
+impl SubmitCmd {
+	/// Run the command.
+	pub fn run(&self) -> Result<(), Error> {
+		let extrinsic = if self.extrinsic.starts_with("0x") {
+			self.extrinsic.clone()
+		} else {
+			fs::read_to_string(&self.extrinsic)?.trim().to_string()
+		};
+		let decoded = hex::decode(extrinsic.trim_start_matches("0x"))
+			.map_err(|e| format!("Invalid extrinsic hex: {}", e))?;
+		UncheckedExtrinsic::decode(&mut &decoded[..])
+			.map_err(|e| format!("Not an extrinsic of this runtime: {:?}", e))?;
+
+		let response = rpc_call(&self.url, "author_submitExtrinsic", json!([extrinsic]))?;
+		println!("{}", response);
+		Ok(())
+	}
+}
+
+/// Call `method` on the node at `url` over HTTP and return the result.
+pub fn rpc_call(url: &str, method: &str, params: Value) -> Result<Value, Error> {
+	let request = hyper::Request::post(url)
+		.header("Content-Type", "application/json")
+		.body(hyper::Body::from(
+			json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }).to_string(),
+		))
+		.map_err(|e| format!("Invalid request to {}: {}", url, e))?;
+
+	let mut runtime = tokio::runtime::Runtime::new()?;
+	let body = runtime.block_on(async {
+		let response = hyper::Client::new().request(request).await?;
+		hyper::body::to_bytes(response.into_body()).await
+	}).map_err(|e| format!("Request to {} failed: {}", url, e))?;
+
+	let mut response: Value = serde_json::from_slice(&body)
+		.map_err(|e| format!("Invalid response from {}: {}", url, e))?;
+	match response.get_mut("result") {
+		Some(result) => Ok(result.take()),
+		None => Err(format!("{} failed: {}", method, response.get("error").unwrap_or(&response)).into()),
+	}
+}
+
+fn write_output(path: Option<&Path>, content: &str) -> Result<(), Error> {
+	match path {
+		Some(path) => fs::write(path, format!("{}\n", content))?,
+		None => println!("{}", content),
+	}
+	Ok(())
+}
@@ -0,0 +1,110 @@
+//! `verify-runtime`: check that the runtime code on chain, or in a state
+//! snapshot, is exactly a locally built wasm blob.
+//!
+//! Council members voting on a `set_code` proposal can build the runtime from
+//! the proposed source, enact the proposal on a local copy of the chain and
+//! compare, or compare the hash the proposal carries.
+
+use std::{path::{Path, PathBuf}, sync::Arc};
+use serde_json::Value;
+use structopt::StructOpt;
+use sc_cli::{BlockNumberOrHash, CliConfiguration, Error, ImportParams, SharedParams};
+use sc_client_api::{Backend, StateBackend};
+use sp_blockchain::HeaderBackend;
+use sp_core::{hexdisplay::HexDisplay, storage::well_known_keys};
+use sp_runtime::{generic::BlockId, traits::Header as HeaderT};
+use litentry_runtime::opaque::Block;
+
+use crate::wasm_blob::WasmBlob;
+
+#[derive(Debug, StructOpt)]
+pub struct VerifyRuntimeCmd {
+	/// Block hash or number to read the code at. Defaults to the best block.
+	#[structopt(value_name = "HASH or NUMBER", conflicts_with = "snapshot")]
+	pub block: Option<BlockNumberOrHash>,
+
+	/// Read the code from a raw chain spec, e.g. one written by `export-state`,
+	/// instead of the database.
+	#[structopt(long, value_name = "PATH", parse(from_os_str))]
+	pub snapshot: Option<PathBuf>,
+
+	/// Wasm blob to compare with. Defaults to the runtime built into the node.
+	#[structopt(long, value_name = "PATH", parse(from_os_str))]
+	pub wasm: Option<PathBuf>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub import_params: ImportParams,
+}
+
+impl VerifyRuntimeCmd {
+	/// Compare with the code in the database.
+	pub fn run<C, B>(&self, client: Arc<C>, backend: Arc<B>) -> Result<(), Error> where
+		C: HeaderBackend<Block>,
+		B: Backend<Block>,
+	{
+		let at = match &self.block {
+			Some(block) => block.parse::<Block>()?,
+			None => BlockId::Hash(client.info().best_hash),
+		};
+		let header = client.header(at)?
+			.ok_or_else(|| format!("Block {:?} not found", at))?;
+		let code = backend.state_at(BlockId::Hash(header.hash()))
+			.map_err(|e| format!("State of {:?} unavailable: {:?}", at, e))?
+			.storage(well_known_keys::CODE)
+			.map_err(|e| format!("Failed to read the code at {:?}: {:?}", at, e))?
+			.ok_or_else(|| format!("No runtime code at {:?}", at))?;
+
+		self.compare(&format!("block #{} ({})", header.number(), header.hash()), WasmBlob::new(code))
+	}
+
+	/// Compare with the code in `--snapshot`.
+	pub fn run_snapshot(&self, snapshot: &Path) -> Result<(), Error> {
+		let spec: Value = serde_json::from_slice(&std::fs::read(snapshot)?)
+			.map_err(|e| format!("Failed to parse {}: {}", snapshot.display(), e))?;
+		let key = format!("0x{}", HexDisplay::from(&well_known_keys::CODE));
+		let code = spec["genesis"]["raw"]["top"][&key].as_str()
+			.ok_or_else(|| format!("{} is not a raw chain spec with `:code`", snapshot.display()))?;
+		let code = hex::decode(code.trim_start_matches("0x"))
+			.map_err(|e| format!("Invalid `:code` in {}: {}", snapshot.display(), e))?;
+
+		self.compare(&snapshot.display().to_string(), WasmBlob::new(code))
+	}
+
+	fn compare(&self, source: &str, on_chain: WasmBlob) -> Result<(), Error> {
+		let (local_source, local) = match &self.wasm {
+			Some(path) => (path.display().to_string(), WasmBlob::load(path)?),
+			None => ("the node's WASM_BINARY".to_string(), WasmBlob::native()?),
+		};
+
+		for (name, blob) in [(source, &on_chain), (local_source.as_str(), &local)].iter() {
+			println!("{}:", name);
+			println!("  hash:    0x{}", HexDisplay::from(&blob.hash()));
+			println!("  size:    {} bytes", blob.code.len());
+			match blob.version() {
+				Ok(version) => println!("  version: {}", version),
+				Err(e) => println!("  version: unavailable, {}", e),
+			}
+		}
+
+		if on_chain.hash() != local.hash() {
+			return Err(format!("The runtime code of {} is not {}", source, local_source).into());
+		}
+		println!("The runtime code of {} is {}", source, local_source);
+		Ok(())
+	}
+}
+
+impl CliConfiguration for VerifyRuntimeCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn import_params(&self) -> Option<&ImportParams> {
+		Some(&self.import_params)
+	}
+}
@@ -0,0 +1,75 @@
+//! Runtime wasm blobs that aren't part of a chain: queried for their version and
+//! metadata by executing them directly, without a client or database.
+
+use std::path::Path;
+use codec::Decode;
+use sc_executor::{WasmExecutionMethod, WasmExecutor};
+use sp_core::{hashing::blake2_256, traits::{CallInWasm, MissingHostFunctions}};
+use sp_state_machine::BasicExternalities;
+use sp_version::RuntimeVersion;
+use sp_wasm_interface::HostFunctions;
+
+use crate::scale::Metadata;
+
+/// Heap pages for the calls. `Metadata_metadata` needs more than the default.
+const HEAP_PAGES: u64 = 2048;
+
+/// A runtime wasm blob.
+pub struct WasmBlob {
+	pub code: Vec<u8>,
+	executor: WasmExecutor,
+}
+
+impl WasmBlob {
+	/// The blob `code`.
+	pub fn new(code: Vec<u8>) -> Self {
+		let executor = WasmExecutor::new(
+			WasmExecutionMethod::Interpreted,
+			Some(HEAP_PAGES),
+			sp_io::SubstrateHostFunctions::host_functions(),
+			1,
+			None,
+		);
+		WasmBlob { code, executor }
+	}
+
+	/// The blob in the file at `path`.
+	pub fn load(path: &Path) -> Result<Self, String> {
+		std::fs::read(path)
+			.map(Self::new)
+			.map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+	}
+
+	/// The runtime compiled into the node.
+	pub fn native() -> Result<Self, String> {
+		litentry_runtime::WASM_BINARY
+			.map(|code| Self::new(code.to_vec()))
+			.ok_or_else(|| "The node was built without the wasm runtime (SKIP_WASM_BUILD)".into())
+	}
+
+	/// Blake2-256 hash of the blob, as in `System::CodeUpdated` and `set_code` proposals.
+	pub fn hash(&self) -> [u8; 32] {
+		blake2_256(&self.code)
+	}
+
+	/// Call the runtime API function `method` and decode its result.
+	pub fn call<R: Decode>(&self, method: &str, data: &[u8]) -> Result<R, String> {
+		let mut ext = BasicExternalities::default();
+		let result = self.executor
+			.call_in_wasm(&self.code, None, method, data, &mut ext, MissingHostFunctions::Allow)
+			.map_err(|e| format!("Failed to call `{}`: {}", method, e))?;
+
+		R::decode(&mut &result[..]).map_err(|e| format!("Invalid result of `{}`: {}", method, e))
+	}
+
+	/// The `RuntimeVersion` of the blob.
+	pub fn version(&self) -> Result<RuntimeVersion, String> {
+		self.call("Core_version", &[])
+	}
+
+	/// The metadata of the blob.
+	pub fn metadata(&self) -> Result<Metadata, String> {
+		let metadata: Vec<u8> = self.call("Metadata_metadata", &[])?;
+		Metadata::from_encoded(&metadata)
+	}
+}
@@ -34,12 +34,13 @@ pub use pallet_balances::Call as BalancesCall;
 pub use sp_runtime::{ModuleId, Percent, Permill, Perbill};
 pub use frame_support::{
 	construct_runtime, parameter_types, StorageValue, debug, RuntimeDebug,
-	traits::{KeyOwnerProofSystem, Randomness, InstanceFilter},
+	traits::{Get, KeyOwnerProofSystem, Randomness, InstanceFilter},
 	weights::{
 		constants::{BlockExecutionWeight, ExtrinsicBaseWeight, RocksDbWeight, WEIGHT_PER_SECOND},
 		DispatchClass, IdentityFee, Weight,
 	},
 };
+use frame_support::{storage::StoragePrefixedMap, Blake2_128Concat, ReversibleStorageHasher, StorageMap};
 use frame_system::{
 	EnsureRoot, EnsureOneOf,
 };
@@ -51,6 +52,9 @@ pub use pallet_account_linker;
 /// Import the offchain-worker pallet.
 pub use pallet_offchain_worker;
 
+/// Reverse index of the account-linker pallet's links.
+pub mod link_index;
+
 /// An index to a block.
 pub type BlockNumber = u32;
 
@@ -105,7 +109,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("litentry-node"),
 	impl_name: create_runtime_str!("litentry-node"),
 	authoring_version: 1,
-	spec_version: 1,
+	spec_version: 2,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
 	transaction_version: 1,
@@ -530,8 +534,8 @@ impl pallet_treasury::Config for Runtime {
 
 /// Configure the template pallet in pallets/template.
 impl pallet_account_linker::Config for Runtime {
-	type Event = Event;
-	type WeightInfo = pallet_account_linker::weights::SubstrateWeight<Runtime>;
+	type Event = LinkerEvent;
+	type WeightInfo = LinkerWeights;
 }
 
 // We need to define the Transaction signer for that using the Key definition
@@ -561,6 +565,100 @@ impl pallet_offchain_worker::Config for Runtime {
 	type WeightInfo = pallet_offchain_worker::weights::SubstrateWeight<Runtime>;
 }
 
+parameter_types! {
+	/// Most addresses of each kind the account linker keeps for an account.
+	pub const MaxLinks: u32 = 3;
+	/// Accounts whose links `LinkIndex` indexes per block after it is added.
+	pub const LinkBackfillBatch: u32 = 50;
+}
+
+/// The links of `AccountLinkerModule`, indexed by `LinkIndex`.
+pub struct LinkerLinks;
+
+impl link_index::Links<AccountId> for LinkerLinks {
+	fn ethereum(account: &AccountId) -> Vec<[u8; 20]> {
+		pallet_account_linker::EthereumLink::<Runtime>::get(account)
+	}
+
+	fn bitcoin(account: &AccountId) -> Vec<Vec<u8>> {
+		pallet_account_linker::BitcoinLink::<Runtime>::get(account)
+	}
+
+	fn ethereum_accounts(after: Option<&AccountId>, count: u32) -> Vec<AccountId> {
+		linked_accounts::<pallet_account_linker::EthereumLink<Runtime>, _>(after, count)
+	}
+
+	fn bitcoin_accounts(after: Option<&AccountId>, count: u32) -> Vec<AccountId> {
+		linked_accounts::<pallet_account_linker::BitcoinLink<Runtime>, _>(after, count)
+	}
+}
+
+/// Up to `count` accounts of the account linker's map `M` following `after`, in
+/// storage order.
+fn linked_accounts<M, V>(after: Option<&AccountId>, count: u32) -> Vec<AccountId> where
+	V: codec::FullCodec,
+	M: StoragePrefixedMap<V> + StorageMap<AccountId, V>,
+{
+	let prefix = M::final_prefix();
+	let mut key = after.map_or_else(|| prefix.to_vec(), M::hashed_key_for);
+	let mut accounts = Vec::new();
+	while accounts.len() < count as usize {
+		key = match sp_io::storage::next_key(&key) {
+			Some(next) if next.starts_with(&prefix) => next,
+			_ => break,
+		};
+		// The maps are `blake2_128_concat`, so the account follows its hash.
+		if let Ok(account) = AccountId::decode(&mut Blake2_128Concat::reverse(&key[prefix.len()..])) {
+			accounts.push(account);
+		}
+	}
+	accounts
+}
+
+impl link_index::Config for Runtime {
+	type Links = LinkerLinks;
+	type MaxLinks = MaxLinks;
+	type BackfillBatch = LinkBackfillBatch;
+}
+
+/// Weights of the account linker's calls, plus indexing the account they link
+/// in `LinkIndex`, so `CheckWeight` holds the indexing to the limits of the
+/// call's class.
+pub struct LinkerWeights;
+
+impl pallet_account_linker::WeightInfo for LinkerWeights {
+	fn link_eth() -> Weight {
+		<pallet_account_linker::weights::SubstrateWeight<Runtime> as pallet_account_linker::WeightInfo>::link_eth()
+			.saturating_add(LinkIndex::note_weight())
+	}
+
+	fn link_btc() -> Weight {
+		<pallet_account_linker::weights::SubstrateWeight<Runtime> as pallet_account_linker::WeightInfo>::link_btc()
+			.saturating_add(LinkIndex::note_weight())
+	}
+}
+
+/// Event type of the account linker, which notes the account of each of its
+/// events in `LinkIndex` on the way to `System`. The account linker has no
+/// other hook for changed links; the work is paid for by [`LinkerWeights`].
+pub struct LinkerEvent(Event);
+
+impl From<pallet_account_linker::Event<Runtime>> for LinkerEvent {
+	fn from(event: pallet_account_linker::Event<Runtime>) -> Self {
+		match &event {
+			pallet_account_linker::RawEvent::EthAddressLinked(account, _)
+				| pallet_account_linker::RawEvent::BtcAddressLinked(account, _) => LinkIndex::note_changed(account),
+		}
+		LinkerEvent(event.into())
+	}
+}
+
+impl From<LinkerEvent> for Event {
+	fn from(event: LinkerEvent) -> Self {
+		event.0
+	}
+}
+
 /// Configure the pallet-identity
 type MoreThanHalfCouncil = EnsureOneOf<
 	AccountId,
@@ -676,6 +774,7 @@ construct_runtime!(
 		// Include the custom logic from the template pallet in the runtime.
 		AccountLinkerModule: pallet_account_linker::{Module, Call, Storage, Event<T>},
 		OffchainWorkerModule: pallet_offchain_worker::{Module, Call, Storage, Event<T>},
+		LinkIndex: link_index::{Module, Storage},
 	}
 );
 
@@ -843,6 +942,24 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl link_index::LinkIndexApi<Block, AccountId> for Runtime {
+		fn ethereum_links(account: AccountId) -> Vec<[u8; 20]> {
+			<LinkerLinks as link_index::Links<_>>::ethereum(&account)
+		}
+
+		fn bitcoin_links(account: AccountId) -> Vec<Vec<u8>> {
+			<LinkerLinks as link_index::Links<_>>::bitcoin(&account)
+		}
+
+		fn ethereum_owners(address: [u8; 20]) -> Vec<AccountId> {
+			LinkIndex::ethereum_owners(address)
+		}
+
+		fn bitcoin_owners(address: Vec<u8>) -> Vec<AccountId> {
+			LinkIndex::bitcoin_owners(address)
+		}
+	}
+
 	#[cfg(feature = "runtime-benchmarks")]
 	impl frame_benchmarking::Benchmark<Block> for Runtime {
 		fn dispatch_benchmark(
@@ -0,0 +1,460 @@
+//! Reverse index of `AccountLinkerModule`: the accounts that linked each
+//! Ethereum and Bitcoin address.
+//!
+//! The runtime notes the account of each linker event with
+//! [`Module::note_changed`] and includes [`Module::note_weight`] in the weight of
+//! the linker's calls. At the end of the block the links of the noted accounts
+//! are compared with the ones indexed before, so addresses replaced by a relink
+//! are dropped from the index.
+//!
+//! Links made before the module was added are indexed after the first runtime
+//! upgrade including it, `BackfillBatch` accounts per block.
+
+use sp_std::prelude::*;
+use codec::{Codec, Decode, Encode, FullCodec};
+use frame_support::{
+	decl_module, decl_storage,
+	traits::Get,
+	weights::Weight,
+	RuntimeDebug, StorageMap, StorageValue,
+};
+
+/// Read access to the links of the account linker.
+pub trait Links<AccountId> {
+	/// Ethereum addresses linked to `account`.
+	fn ethereum(account: &AccountId) -> Vec<[u8; 20]>;
+	/// Bitcoin addresses linked to `account`.
+	fn bitcoin(account: &AccountId) -> Vec<Vec<u8>>;
+	/// Up to `count` accounts with Ethereum links, following `after` in a fixed order.
+	fn ethereum_accounts(after: Option<&AccountId>, count: u32) -> Vec<AccountId>;
+	/// Up to `count` accounts with Bitcoin links, following `after` in a fixed order.
+	fn bitcoin_accounts(after: Option<&AccountId>, count: u32) -> Vec<AccountId>;
+}
+
+pub trait Config: frame_system::Config {
+	/// The links to index.
+	type Links: Links<Self::AccountId>;
+	/// Most addresses of each kind `Links` keeps for an account.
+	type MaxLinks: Get<u32>;
+	/// Accounts indexed per block while indexing the links made before the module.
+	type BackfillBatch: Get<u32>;
+}
+
+/// Storage versions of the module.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+enum Releases {
+	/// Links made before the module was added may be missing from the index.
+	V1,
+	/// Every link is indexed.
+	V2,
+}
+
+impl Default for Releases {
+	fn default() -> Self {
+		Releases::V1
+	}
+}
+
+/// Where indexing the links made before the module continues.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+enum Backfill<AccountId> {
+	/// At the accounts with Ethereum links after the given one, or the first.
+	Ethereum(Option<AccountId>),
+	/// At the accounts with Bitcoin links after the given one, or the first.
+	Bitcoin(Option<AccountId>),
+}
+
+decl_storage! {
+	trait Store for Module<T: Config> as LinkIndex {
+		/// Accounts that linked each Ethereum address.
+		pub EthereumOwners get(fn ethereum_owners):
+			map hasher(blake2_128_concat) [u8; 20] => Vec<T::AccountId>;
+		/// Accounts that linked each Bitcoin address.
+		pub BitcoinOwners get(fn bitcoin_owners):
+			map hasher(blake2_128_concat) Vec<u8> => Vec<T::AccountId>;
+		/// The Ethereum links of each account as last indexed.
+		IndexedEthereum: map hasher(blake2_128_concat) T::AccountId => Vec<[u8; 20]>;
+		/// The Bitcoin links of each account as last indexed.
+		IndexedBitcoin: map hasher(blake2_128_concat) T::AccountId => Vec<Vec<u8>>;
+		/// Accounts noted in the current block, indexed in `on_finalize`.
+		Changed: Vec<T::AccountId>;
+		/// Storage version, to index the links made before the module once.
+		StorageVersion: Releases;
+		/// Progress of indexing the links made before the module, if under way.
+		BackfillProgress: Option<Backfill<T::AccountId>>;
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Config> for enum Call where origin: T::Origin {
+		fn on_runtime_upgrade() -> Weight {
+			if StorageVersion::get() == Releases::V2 || BackfillProgress::<T>::exists() {
+				return T::DbWeight::get().reads(2);
+			}
+
+			// Indexed from the next block on, see `on_initialize`.
+			BackfillProgress::<T>::put(Backfill::Ethereum(None));
+			T::DbWeight::get().reads_writes(2, 1)
+		}
+
+		fn on_initialize(_n: T::BlockNumber) -> Weight {
+			// Taking `Changed` in `on_finalize`. Indexing the accounts is charged to the linker's calls.
+			let weight = T::DbWeight::get().reads_writes(2, 1);
+
+			match BackfillProgress::<T>::get() {
+				Some(progress) => weight.saturating_add(Self::backfill(progress)),
+				None => weight,
+			}
+		}
+
+		fn on_finalize(_n: T::BlockNumber) {
+			for account in Changed::<T>::take() {
+				Self::refresh(&account);
+			}
+		}
+	}
+}
+
+impl<T: Config> Module<T> {
+	/// Index the links of `account` at the end of the block, for an event about
+	/// them. The call emitting the event accounts for [`Module::note_weight`].
+	pub fn note_changed(account: &T::AccountId) {
+		Changed::<T>::mutate(|changed| if !changed.contains(account) {
+			changed.push(account.clone());
+		});
+	}
+
+	/// Most weight of noting an account and indexing it at the end of the block.
+	pub fn note_weight() -> Weight {
+		T::DbWeight::get().reads_writes(1, 1).saturating_add(Self::refresh_weight())
+	}
+
+	/// Index the next `BackfillBatch` accounts from `progress`, Ethereum links
+	/// first. Accounts with both kinds of links are indexed twice, the second
+	/// time without changes.
+	fn backfill(progress: Backfill<T::AccountId>) -> Weight {
+		let batch = T::BackfillBatch::get();
+		let (accounts, next) = match progress {
+			Backfill::Ethereum(after) => {
+				let accounts = T::Links::ethereum_accounts(after.as_ref(), batch);
+				let next = match accounts.last() {
+					Some(last) if accounts.len() >= batch as usize => Backfill::Ethereum(Some(last.clone())),
+					_ => Backfill::Bitcoin(None),
+				};
+				(accounts, Some(next))
+			},
+			Backfill::Bitcoin(after) => {
+				let accounts = T::Links::bitcoin_accounts(after.as_ref(), batch);
+				let next = match accounts.last() {
+					Some(last) if accounts.len() >= batch as usize => Some(Backfill::Bitcoin(Some(last.clone()))),
+					_ => None,
+				};
+				(accounts, next)
+			},
+		};
+
+		for account in &accounts {
+			Self::refresh(account);
+		}
+		match next {
+			Some(next) => BackfillProgress::<T>::put(next),
+			None => {
+				BackfillProgress::<T>::kill();
+				StorageVersion::put(Releases::V2);
+			},
+		}
+
+		// Listing the accounts reads one key past the last.
+		let accounts = accounts.len() as Weight;
+		T::DbWeight::get().reads_writes(accounts + 1, 2)
+			.saturating_add(accounts.saturating_mul(Self::refresh_weight()))
+	}
+
+	/// Most weight of [`Module::refresh`]: for each kind of address, the links and
+	/// the indexed links, and the owners of up to `MaxLinks` old and new addresses.
+	fn refresh_weight() -> Weight {
+		let addresses = 2 * T::MaxLinks::get() as Weight;
+		T::DbWeight::get().reads_writes(2 * (2 + addresses), 2 * (1 + addresses))
+	}
+
+	/// Index the current links of `account`.
+	fn refresh(account: &T::AccountId) {
+		Self::reindex::<_, EthereumOwners<T>, IndexedEthereum<T>>(account, T::Links::ethereum(account));
+		Self::reindex::<_, BitcoinOwners<T>, IndexedBitcoin<T>>(account, T::Links::bitcoin(account));
+	}
+
+	/// Replace the indexed links of `account` by `linked`.
+	fn reindex<A, Owners, Indexed>(account: &T::AccountId, linked: Vec<A>) where
+		A: FullCodec + PartialEq,
+		Owners: StorageMap<A, Vec<T::AccountId>, Query = Vec<T::AccountId>>,
+		Indexed: StorageMap<T::AccountId, Vec<A>, Query = Vec<A>>,
+	{
+		let indexed = Indexed::get(account);
+		if indexed == linked {
+			return;
+		}
+
+		for address in indexed.iter().filter(|address| !linked.contains(address)) {
+			let mut owners = Owners::get(address);
+			owners.retain(|owner| owner != account);
+			if owners.is_empty() {
+				Owners::remove(address);
+			} else {
+				Owners::insert(address, owners);
+			}
+		}
+		for address in linked.iter().filter(|address| !indexed.contains(address)) {
+			Owners::mutate(address, |owners| if !owners.contains(account) {
+				owners.push(account.clone());
+			});
+		}
+		if linked.is_empty() {
+			Indexed::remove(account);
+		} else {
+			Indexed::insert(account, linked);
+		}
+	}
+}
+
+sp_api::decl_runtime_apis! {
+	/// Lookup of the links of an account and of the accounts that linked an external address.
+	pub trait LinkIndexApi<AccountId: Codec> {
+		/// Ethereum addresses linked to `account`.
+		fn ethereum_links(account: AccountId) -> Vec<[u8; 20]>;
+		/// Bitcoin addresses linked to `account`.
+		fn bitcoin_links(account: AccountId) -> Vec<Vec<u8>>;
+		/// Accounts that linked the Ethereum address `address`.
+		fn ethereum_owners(address: [u8; 20]) -> Vec<AccountId>;
+		/// Accounts that linked the Bitcoin address `address`.
+		fn bitcoin_owners(address: Vec<u8>) -> Vec<AccountId>;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::link_index;
+	use frame_support::{
+		parameter_types,
+		traits::{OnFinalize, OnInitialize, OnRuntimeUpgrade},
+		weights::RuntimeDbWeight,
+	};
+	use sp_core::H256;
+	use sp_runtime::{testing::Header, traits::{BlakeTwo256, IdentityLookup}};
+	use std::{cell::RefCell, collections::BTreeMap};
+
+	type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+	type Block = frame_system::mocking::MockBlock<Test>;
+
+	frame_support::construct_runtime!(
+		pub enum Test where
+			Block = Block,
+			NodeBlock = Block,
+			UncheckedExtrinsic = UncheckedExtrinsic,
+		{
+			System: frame_system::{Module, Call, Config, Storage, Event<T>},
+			LinkIndex: link_index::{Module, Storage},
+		}
+	);
+
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaxLinks: u32 = 3;
+		pub const BackfillBatch: u32 = 2;
+		pub const TestDbWeight: RuntimeDbWeight = RuntimeDbWeight { read: 1, write: 10 };
+	}
+
+	impl frame_system::Config for Test {
+		type BaseCallFilter = ();
+		type BlockWeights = ();
+		type BlockLength = ();
+		type DbWeight = TestDbWeight;
+		type Origin = Origin;
+		type Call = Call;
+		type Index = u64;
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = Event;
+		type BlockHashCount = BlockHashCount;
+		type Version = ();
+		type PalletInfo = PalletInfo;
+		type AccountData = ();
+		type OnNewAccount = ();
+		type OnKilledAccount = ();
+		type SystemWeightInfo = ();
+		type SS58Prefix = ();
+	}
+
+	thread_local! {
+		static ETHEREUM: RefCell<BTreeMap<u64, Vec<[u8; 20]>>> = Default::default();
+		static BITCOIN: RefCell<BTreeMap<u64, Vec<Vec<u8>>>> = Default::default();
+	}
+
+	/// Links kept by the tests in place of the account linker.
+	pub struct TestLinks;
+
+	impl Links<u64> for TestLinks {
+		fn ethereum(account: &u64) -> Vec<[u8; 20]> {
+			ETHEREUM.with(|links| links.borrow().get(account).cloned().unwrap_or_default())
+		}
+
+		fn bitcoin(account: &u64) -> Vec<Vec<u8>> {
+			BITCOIN.with(|links| links.borrow().get(account).cloned().unwrap_or_default())
+		}
+
+		fn ethereum_accounts(after: Option<&u64>, count: u32) -> Vec<u64> {
+			ETHEREUM.with(|links| accounts_after(&links.borrow(), after, count))
+		}
+
+		fn bitcoin_accounts(after: Option<&u64>, count: u32) -> Vec<u64> {
+			BITCOIN.with(|links| accounts_after(&links.borrow(), after, count))
+		}
+	}
+
+	fn accounts_after<V>(links: &BTreeMap<u64, V>, after: Option<&u64>, count: u32) -> Vec<u64> {
+		links.keys()
+			.filter(|account| after.map_or(true, |after| *account > after))
+			.take(count as usize)
+			.cloned()
+			.collect()
+	}
+
+	impl Config for Test {
+		type Links = TestLinks;
+		type MaxLinks = MaxLinks;
+		type BackfillBatch = BackfillBatch;
+	}
+
+	fn new_test_ext() -> sp_io::TestExternalities {
+		ETHEREUM.with(|links| links.borrow_mut().clear());
+		BITCOIN.with(|links| links.borrow_mut().clear());
+		frame_system::GenesisConfig::default().build_storage::<Test>().unwrap().into()
+	}
+
+	const ETH_A: [u8; 20] = [1; 20];
+	const ETH_B: [u8; 20] = [2; 20];
+
+	/// Replace the links of `account` in a block, as the linker would.
+	fn link(account: u64, ethereum: Vec<[u8; 20]>, bitcoin: Vec<Vec<u8>>) {
+		ETHEREUM.with(|links| links.borrow_mut().insert(account, ethereum));
+		BITCOIN.with(|links| links.borrow_mut().insert(account, bitcoin));
+		LinkIndex::on_initialize(1);
+		LinkIndex::note_changed(&account);
+		LinkIndex::on_finalize(1);
+	}
+
+	#[test]
+	fn link_indexes_addresses() {
+		new_test_ext().execute_with(|| {
+			link(1, vec![ETH_A], vec![b"1BtcA".to_vec()]);
+
+			assert_eq!(LinkIndex::ethereum_owners(ETH_A), vec![1]);
+			assert_eq!(LinkIndex::bitcoin_owners(b"1BtcA".to_vec()), vec![1]);
+			assert!(Changed::<Test>::get().is_empty());
+		});
+	}
+
+	#[test]
+	fn relink_drops_replaced_address() {
+		new_test_ext().execute_with(|| {
+			link(1, vec![ETH_A], vec![]);
+			link(1, vec![ETH_B], vec![]);
+
+			assert!(LinkIndex::ethereum_owners(ETH_A).is_empty());
+			assert!(!EthereumOwners::<Test>::contains_key(ETH_A));
+			assert_eq!(LinkIndex::ethereum_owners(ETH_B), vec![1]);
+		});
+	}
+
+	#[test]
+	fn shared_address_keeps_every_owner() {
+		new_test_ext().execute_with(|| {
+			link(1, vec![ETH_A], vec![]);
+			link(2, vec![ETH_A], vec![]);
+			assert_eq!(LinkIndex::ethereum_owners(ETH_A), vec![1, 2]);
+
+			link(1, vec![ETH_B], vec![]);
+			assert_eq!(LinkIndex::ethereum_owners(ETH_A), vec![2]);
+			assert_eq!(LinkIndex::ethereum_owners(ETH_B), vec![1]);
+		});
+	}
+
+	#[test]
+	fn noting_twice_indexes_once() {
+		new_test_ext().execute_with(|| {
+			LinkIndex::note_changed(&1);
+			LinkIndex::note_changed(&1);
+			LinkIndex::note_changed(&2);
+
+			assert_eq!(Changed::<Test>::get(), vec![1, 2]);
+			// Charged to the linker's calls, which CheckWeight holds to the block limits.
+			assert_eq!(System::block_weight().total(), 0);
+			assert_eq!(
+				LinkIndex::note_weight(),
+				TestDbWeight::get().reads_writes(1, 1) + LinkIndex::refresh_weight(),
+			);
+		});
+	}
+
+	#[test]
+	fn upgrade_indexes_existing_links_in_batches() {
+		new_test_ext().execute_with(|| {
+			ETHEREUM.with(|links| {
+				let mut links = links.borrow_mut();
+				links.insert(1, vec![ETH_A]);
+				links.insert(2, vec![ETH_A]);
+				links.insert(3, vec![ETH_B]);
+			});
+			BITCOIN.with(|links| links.borrow_mut().insert(2, vec![b"1BtcA".to_vec()]));
+
+			LinkIndex::on_runtime_upgrade();
+			assert!(LinkIndex::ethereum_owners(ETH_A).is_empty());
+
+			let most = TestDbWeight::get().reads_writes(2, 1)
+				+ TestDbWeight::get().reads_writes(BackfillBatch::get() as Weight + 1, 2)
+				+ BackfillBatch::get() as Weight * LinkIndex::refresh_weight();
+			assert!(LinkIndex::on_initialize(1) <= most);
+			assert_eq!(LinkIndex::ethereum_owners(ETH_A), vec![1, 2]);
+			assert!(LinkIndex::ethereum_owners(ETH_B).is_empty());
+
+			LinkIndex::on_initialize(2);
+			assert_eq!(LinkIndex::ethereum_owners(ETH_B), vec![3]);
+			assert_eq!(StorageVersion::get(), Releases::V1);
+
+			LinkIndex::on_initialize(3);
+			assert_eq!(LinkIndex::bitcoin_owners(b"1BtcA".to_vec()), vec![2]);
+			assert_eq!(StorageVersion::get(), Releases::V2);
+			assert!(!BackfillProgress::<Test>::exists());
+
+			// Later upgrades leave the index to the events.
+			ETHEREUM.with(|links| links.borrow_mut().insert(4, vec![ETH_A]));
+			LinkIndex::on_runtime_upgrade();
+			LinkIndex::on_initialize(4);
+			assert_eq!(LinkIndex::ethereum_owners(ETH_A), vec![1, 2]);
+		});
+	}
+
+	#[test]
+	fn upgrade_during_backfill_keeps_progress() {
+		new_test_ext().execute_with(|| {
+			ETHEREUM.with(|links| {
+				let mut links = links.borrow_mut();
+				links.insert(1, vec![ETH_A]);
+				links.insert(2, vec![ETH_A]);
+				links.insert(3, vec![ETH_B]);
+			});
+
+			LinkIndex::on_runtime_upgrade();
+			LinkIndex::on_initialize(1);
+			LinkIndex::on_runtime_upgrade();
+			assert_eq!(BackfillProgress::<Test>::get(), Some(Backfill::Ethereum(Some(2))));
+
+			LinkIndex::on_initialize(2);
+			assert_eq!(LinkIndex::ethereum_owners(ETH_B), vec![3]);
+		});
+	}
+}
@@ -37,5 +37,13 @@ stopNodes() {
 # stop all nodes
 stopNodes
 
+# 4. With `--offline`, answer the offchain workers' HTTP requests, the token
+#    server's included, from the fixtures in `ocw-fixtures`
+OCW_ARGS=
+if [[ "$1" == "--offline" ]]
+then
+    OCW_ARGS="--offchain-http-mock $SCRIPT_DIR/ocw-fixtures"
+fi
+
 echo "Starting dev node ..."
-$EXECUTOR --tmp --dev --rpc-external --ws-external --rpc-methods Unsafe --rpc-cors all --alice
+$EXECUTOR --tmp --dev --rpc-external --ws-external --rpc-methods Unsafe --rpc-cors all --alice $OCW_ARGS
@@ -4,6 +4,7 @@ import { spawn, ChildProcess } from "child_process";
 import 'mocha';
 import { ApiPromise, Keyring, WsProvider } from "@polkadot/api";
 import { KeyringPair } from '@polkadot/keyring/types';
+import { typesBundle } from '../types';
 //import  '@polkadot/types/interfaces/system'
 
 export const BINARY_PATH = `../target/debug/litentry-node`;
@@ -104,11 +105,9 @@ export async function initApiPromise(wsProvider: WsProvider) {
 	// Initiate the polkadot API.
 	const api = await ApiPromise.create({
 		provider: wsProvider,
+		// generated by `litentry-node export-types`
+		typesBundle,
 		types: {
-			// mapping the actual specified address format
-			Address: "AccountId",
-			// mapping the lookup
-			LookupSource: "AccountId",
 			Account: {
 				nonce: "U256",
 				balance: "U256"
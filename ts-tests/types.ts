@@ -0,0 +1,30 @@
+// Generated by `litentry-node export-types`. Do not edit.
+
+export const typesBundle = {
+  "spec": {
+    "litentry-node": {
+      "types": [
+        {
+          "minmax": [
+            1,
+            null
+          ],
+          "types": {
+            "Address": "AccountId",
+            "LookupSource": "AccountId",
+            "EthAddress": "[u8; 20]",
+            "ProxyType": {
+              "_enum": [
+                "Any",
+                "NonTransfer",
+                "Governance"
+              ]
+            }
+          }
+        }
+      ]
+    }
+  }
+};
+
+export default typesBundle;